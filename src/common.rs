@@ -1,32 +1,148 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Error, Formatter};
+use std::ops::{Add, Mul, Sub};
 use std::time::SystemTime;
 
 use dynamics::Cluster;
 use geomath::matrix::Matrix3;
 use geomath::prelude::transforms::Rotation3;
-use geomath::prelude::Algebra;
+use geomath::prelude::{Algebra, Metric};
 use geomath::vector::*;
 use geomath::{matrix, vector};
 use piston::input::{Key, MouseButton};
-use serde::export::fmt::{Error, Formatter};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 use unitflow::date::Duration;
 
-use crate::keys::*;
+use crate::keys::KeyMap;
 
 pub static HOLD: Direction = Direction::Hold;
 
 pub static DEFAULT_ANGLE_INCREMENT: f64 = std::f64::consts::FRAC_PI_8 / 6.;
 pub const SPEED_SCALING_FACTOR: f64 = 5e-7;
 pub const TRANSLATION_SCALING_FACTOR: f64 = 100.;
+pub const ZOOM_SCALING_FACTOR: f64 = 1.1;
+pub const FOLLOW_SMOOTHING_FACTOR: f64 = 0.1;
+pub const DUPLICATE_OFFSET: f64 = 1e-2;
+pub const RANDOM_MASS_RANGE: (f64, f64) = (1e22, 1e27);
+pub const RANDOM_DENSITY: f64 = 5.5e3;
+pub const AUTO_ROTATE_PERIOD: u64 = 8;
+pub const DEFAULT_AWAY_SIGMA: f64 = 10e2;
+pub const DEFAULT_DRAG_COEFFICIENT: f64 = 1e-12;
+pub const DEFAULT_SPRING_STIFFNESS: f64 = 1e-12;
+pub const DEFAULT_HALO_VELOCITY: f64 = 2.2e5;
+pub const DEFAULT_HALO_CORE_RADIUS: f64 = 1e20;
+pub const DEFAULT_CHARGE_STEP: f64 = 1e14;
+
+pub const DEFAULT_MAX_TIME_SCALE: f64 = 1e12;
+pub const DEFAULT_MIN_STEPS_PER_ORBIT: f64 = 100.;
+
+pub const MIN_DISTANCE_SCALE: f64 = 1e-24;
+pub const MAX_DISTANCE_SCALE: f64 = 1e6;
 
 pub const DEFAULT_WINDOW_SIZE: [f64; 2] = [640., 640.];
 pub const DEFAULT_OVERSAMPLING: u32 = 1024;
+pub const MIN_OVERSAMPLING: u32 = 1;
+pub const MAX_OVERSAMPLING: u32 = 1 << 20;
+pub const DEFAULT_TRAJECTORY_LENGTH: usize = 256;
+pub const MIN_TRAJECTORY_LENGTH: usize = 2;
+pub const MAX_TRAJECTORY_LENGTH: usize = 65536;
+pub const HEADLESS_DT: f64 = 1. / 60.;
+pub const DEFAULT_FPS: u64 = 60;
+pub const DEFAULT_UPS: u64 = 60;
+pub const DEFAULT_VELOCITY_SCALE: f64 = 1e-3;
+pub const HISTORY_SIZE: usize = 3600;
+pub const UNDO_SIZE: usize = 32;
+pub const DEFAULT_POTENTIAL_RESOLUTION: f64 = 16.;
+pub const PREDICTION_STEPS: usize = 512;
+pub const PREDICTION_BUDGET: usize = 1 << 20;
+pub const PREDICTION_HORIZON: f64 = 10.;
 
 pub const BLACK: [f32; 4] = [0., 0., 0., 1.];
 pub const WHITE: [f32; 4] = [1., 1., 1., 1.];
 pub const RED: [f32; 4] = [1., 0., 0., 1.];
 pub const GREEN: [f32; 4] = [0., 1., 0., 1.];
 pub const BLUE: [f32; 4] = [0., 0., 1., 1.];
+pub const YELLOW: [f32; 4] = [1., 1., 0., 1.];
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Palette {
+    pub background: [f32; 4],
+    pub foreground: [f32; 4],
+    pub bodies: Vec<[f32; 4]>,
+}
+
+impl Palette {
+    pub fn dark() -> Palette {
+        Palette {
+            background: BLACK,
+            foreground: WHITE,
+            bodies: vec![
+                [1., 0.8, 0.3, 1.],
+                [0.4, 0.7, 1., 1.],
+                [1., 0.4, 0.4, 1.],
+                [0.5, 1., 0.5, 1.],
+                [0.9, 0.5, 1., 1.],
+                [0.4, 1., 0.9, 1.],
+            ],
+        }
+    }
+
+    pub fn light() -> Palette {
+        Palette {
+            background: WHITE,
+            foreground: BLACK,
+            bodies: vec![
+                [0.1, 0.1, 0.5, 1.],
+                [0.6, 0.1, 0.1, 1.],
+                [0.1, 0.45, 0.1, 1.],
+                [0.4, 0.1, 0.5, 1.],
+                [0.45, 0.3, 0.1, 1.],
+                [0.2, 0.3, 0.35, 1.],
+            ],
+        }
+    }
+
+    pub fn color(&self, index: usize) -> [f32; 4] {
+        if self.bodies.is_empty() {
+            return self.foreground;
+        }
+        self.bodies[index % self.bodies.len()]
+    }
+}
+
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        None => StdRng::from_entropy(),
+        Some(seed) => StdRng::seed_from_u64(seed),
+    }
+}
+
+#[inline]
+pub fn cross(lhs: &Vector3, rhs: &Vector3) -> Vector3 {
+    vec3(
+        lhs.y * rhs.z - lhs.z * rhs.y,
+        lhs.z * rhs.x - lhs.x * rhs.z,
+        lhs.x * rhs.y - lhs.y * rhs.x,
+    )
+}
+
+#[inline]
+pub fn cross_z(lhs: &Vector2, rhs: &Vector2) -> f64 {
+    lhs.x * rhs.y - lhs.y * rhs.x
+}
+
+#[inline]
+pub fn lerp<T>(from: T, to: T, t: f64) -> T
+    where T: Copy + Add<Output=T> + Sub<Output=T> + Mul<f64, Output=T> {
+    from + (to - from) * t
+}
+
+#[inline]
+pub fn lerp_clamped<T>(from: T, to: T, t: f64) -> T
+    where T: Copy + Add<Output=T> + Sub<Output=T> + Mul<f64, Output=T> {
+    lerp(from, to, t.max(0.).min(1.))
+}
 
 #[derive(Copy, Clone)]
 pub struct Average {
@@ -63,6 +179,71 @@ impl Debug for Average {
     }
 }
 
+#[derive(Clone)]
+pub struct History {
+    pub initial: Option<f64>,
+    pub last: f64,
+    pub max_drift: f64,
+    values: Vec<f64>,
+    index: usize,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            initial: None,
+            last: 0.,
+            max_drift: 0.,
+            values: Vec::with_capacity(HISTORY_SIZE),
+            index: 0,
+        }
+    }
+
+    pub fn push(&mut self, val: f64) -> &mut Self {
+        if self.initial.is_none() {
+            self.initial = Some(val);
+        }
+        if self.values.len() < HISTORY_SIZE {
+            self.values.push(val);
+        } else {
+            self.values[self.index] = val;
+        }
+        self.index = (self.index + 1) % HISTORY_SIZE;
+        self.last = val;
+        self.max_drift = self.max_drift.max(self.drift());
+        self
+    }
+
+    pub fn clear(&mut self) -> &mut Self {
+        self.initial = None;
+        self.max_drift = 0.;
+        self.values.clear();
+        self.index = 0;
+        self
+    }
+
+    pub fn min(&self) -> f64 {
+        self.values.iter().cloned().fold(std::f64::INFINITY, f64::min)
+    }
+
+    pub fn max(&self) -> f64 {
+        self.values.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max)
+    }
+
+    pub fn drift(&self) -> f64 {
+        match self.initial {
+            None => 0.,
+            Some(initial) => if initial == 0. { 0. } else { ((self.last - initial) / initial).abs() },
+        }
+    }
+}
+
+impl Debug for History {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.last)
+    }
+}
+
 pub struct Statistics {
     pub mean: f64,
     pub deviation: f64,
@@ -139,24 +320,22 @@ pub enum Direction {
     Hold = 0,
 }
 
-impl From<Key> for Direction {
-    fn from(key: Key) -> Self {
+impl Direction {
+    pub fn from_key(key: &Key, keys: &KeyMap) -> Direction {
         use Direction::*;
-        if key == KEY_DIRECTION_LEFT {
+        if *key == keys.direction_left {
             Left
-        } else if key == KEY_DIRECTION_RIGHT {
+        } else if *key == keys.direction_right {
             Right
-        } else if key == KEY_DIRECTION_UP {
+        } else if *key == keys.direction_up {
             Up
-        } else if key == KEY_DIRECTION_DOWN {
+        } else if *key == keys.direction_down {
             Down
         } else {
             Hold
         }
     }
-}
 
-impl Direction {
     pub fn opposite(&self, other: &Direction) -> bool {
         let self_val = *self as i8;
         let other_val = *other as i8;
@@ -177,6 +356,7 @@ impl Direction {
 
 #[derive(Clone, Copy)]
 pub struct Orientation {
+    angles: [f64; 3],
     rotation: Matrix3,
     inverse_rotation: Matrix3,
     rotation_x: Matrix3,
@@ -193,6 +373,7 @@ pub struct Orientation {
 impl Orientation {
     pub fn new(angle_x: f64, angle_y: f64, angle_z: f64) -> Orientation {
         let mut ret = Orientation {
+            angles: [angle_x, angle_y, angle_z],
             rotation: matrix::consts::EYE_3,
             inverse_rotation: matrix::consts::EYE_3,
             rotation_x: Matrix3::from_rotation_x(angle_x),
@@ -213,41 +394,70 @@ impl Orientation {
         Orientation::new(0., 0., 0.)
     }
 
+    pub fn from_normal(normal: &Vector3) -> Option<Self> {
+        let length = normal.magnitude();
+        if length == 0. || !length.is_finite() {
+            return None;
+        }
+        let angle_x = normal.y.atan2(normal.z);
+        let angle_y = (-normal.x).atan2((normal.y * normal.y + normal.z * normal.z).sqrt());
+        Some(Orientation::new(angle_x, angle_y, 0.))
+    }
+
     pub fn increment_x(&mut self) -> &mut Self {
+        self.angles[0] += DEFAULT_ANGLE_INCREMENT;
         self.rotation_x *= self.increment_x;
         self.update_rotation();
         self
     }
 
     pub fn increment_y(&mut self) -> &mut Self {
+        self.angles[1] += DEFAULT_ANGLE_INCREMENT;
         self.rotation_y *= self.increment_y;
         self.update_rotation();
         self
     }
 
     pub fn increment_z(&mut self) -> &mut Self {
+        self.angles[2] += DEFAULT_ANGLE_INCREMENT;
         self.rotation_z *= self.increment_z;
         self.update_rotation();
         self
     }
     pub fn decrement_x(&mut self) -> &mut Self {
+        self.angles[0] -= DEFAULT_ANGLE_INCREMENT;
         self.rotation_x *= self.decrement_x;
         self.update_rotation();
         self
     }
 
     pub fn decrement_y(&mut self) -> &mut Self {
+        self.angles[1] -= DEFAULT_ANGLE_INCREMENT;
         self.rotation_y *= self.decrement_y;
         self.update_rotation();
         self
     }
 
     pub fn decrement_z(&mut self) -> &mut Self {
+        self.angles[2] -= DEFAULT_ANGLE_INCREMENT;
         self.rotation_z *= self.decrement_z;
         self.update_rotation();
         self
     }
 
+    pub fn reset(&mut self) -> &mut Self {
+        self.angles = [0.; 3];
+        self.rotation_x = matrix::consts::EYE_3;
+        self.rotation_y = matrix::consts::EYE_3;
+        self.rotation_z = matrix::consts::EYE_3;
+        self.update_rotation();
+        self
+    }
+
+    pub fn angles(&self) -> [f64; 3] {
+        self.angles
+    }
+
     pub fn rotation(&self) -> Matrix3 {
         self.rotation
     }
@@ -272,10 +482,13 @@ impl Debug for Orientation {
 #[derive(Clone, Copy, Debug)]
 pub struct Step {
     pub count: u32,
+    pub dt: f64,
     pub total: Duration,
     pub simulated: Duration,
+    pub elapsed: f64,
     pub frame: Average,
     pub system: Average,
+    pub scaled: Average,
     time: SystemTime,
 }
 
@@ -283,10 +496,13 @@ impl Step {
     pub fn new() -> Step {
         Step {
             count: 0,
+            dt: 0.,
             total: Duration::from(0.),
             simulated: Duration::from(0.),
+            elapsed: 0.,
             frame: Average::new(),
             system: Average::new(),
+            scaled: Average::new(),
             time: SystemTime::now(),
         }
     }
@@ -297,13 +513,27 @@ impl Step {
             .push(time.duration_since(self.time).unwrap().as_secs_f64());
         self.time = time;
         self.frame.push(dt);
+        self.scaled.push((dt * scale).abs());
         self.total += dt;
-        self.simulated += dt * scale;
+        self.elapsed += dt * scale;
+        self.simulated = Duration::from(self.elapsed.abs());
         self.count = (self.count + 1) % std::u32::MAX;
     }
+
+    pub fn real_time_factor(&self) -> Option<f64> {
+        let system = self.system.value();
+        if system <= 0. {
+            return None;
+        }
+        Some(self.scaled.value() / system)
+    }
+
+    pub fn hold(&mut self) {
+        self.time = SystemTime::now();
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub struct Scale {
     pub time: f64,
     pub distance: f64,
@@ -318,8 +548,8 @@ impl Scale {
         Scale::new(1., 1.)
     }
 
-    pub fn increase_time(&mut self) {
-        self.time *= 2.;
+    pub fn increase_time(&mut self, max: f64) {
+        self.time = (self.time * 2.).min(max.max(self.time));
     }
 
     pub fn decrease_time(&mut self) {
@@ -333,4 +563,11 @@ impl Scale {
     pub fn decrease_distance(&mut self) {
         self.distance /= 2.;
     }
+
+    pub fn zoom_distance(&mut self, factor: f64) -> f64 {
+        let distance = (self.distance * factor).max(MIN_DISTANCE_SCALE).min(MAX_DISTANCE_SCALE);
+        let factor = distance / self.distance;
+        self.distance = distance;
+        factor
+    }
 }
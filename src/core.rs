@@ -1,19 +1,27 @@
 use std::cmp::{max, min};
 use std::error::Error;
+use std::fs;
+use std::path::Path;
 
 use dynamics::{Cluster, orbital};
-use dynamics::orbital::Body;
+use dynamics::orbital::{Body, Orbit};
 use dynamics::point::Point3;
 use dynamics::solver::{Method, Solver};
 use geomath::point;
-use geomath::vector::Vector6;
+use geomath::prelude::Metric;
+use geomath::vector;
+use geomath::vector::{vec3, Vector3, Vector6};
 use getopts::Options;
 use piston::input::{Key, MouseButton};
 use piston::window::Size;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::common::*;
 use crate::keys::*;
+use crate::orbit::Kepler;
+use crate::physics;
+use crate::physics::{Field, G_UNIV, Halo};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum State {
@@ -21,17 +29,20 @@ pub enum State {
     Translate,
     Add,
     Remove,
+    Select,
     WaitDrop,
     WaitSpeed,
     CancelDrop,
+    Anchor,
+    Measure,
     Reset,
 }
 
 impl State {
-    pub fn next(&mut self, key: &Key, button: &MouseButton) {
+    pub fn next(&mut self, key: &Key, button: &MouseButton, keys: &KeyMap) {
         use State::*;
 
-        if *key == KEY_RESET {
+        if *key == keys.reset {
             *self = Reset;
             return;
         }
@@ -40,40 +51,57 @@ impl State {
             Reset => Move,
             Add => WaitDrop,
             Remove => Move,
+            Select => Move,
             CancelDrop => Move,
-            Move => if *button == MOUSE_MOVE_ADD {
+            Move => if *button == keys.move_add {
                 Add
-            } else if *button == MOUSE_MOVE_REMOVE {
+            } else if *button == keys.move_remove {
                 Remove
-            } else if *key == KEY_TOGGLE_TRANSLATE {
+            } else if *button == keys.move_select {
+                Select
+            } else if *key == keys.toggle_translate {
                 Translate
+            } else if *key == keys.measure {
+                Anchor
             } else {
                 *self
             },
-            Translate => if *key == KEY_TOGGLE_TRANSLATE {
+            Translate => if *key == keys.toggle_translate {
                 Move
             } else {
                 *self
             },
-            WaitDrop => if *button == MOUSE_WAIT_DROP_DO {
+            WaitDrop => if *button == keys.wait_drop_do {
                 WaitSpeed
-            } else if *button == MOUSE_WAIT_DROP_CANCEL {
+            } else if *button == keys.wait_drop_cancel {
                 CancelDrop
             } else {
                 *self
             }
-            WaitSpeed => if *button == MOUSE_WAIT_DROP_DO {
+            WaitSpeed => if *button == keys.wait_drop_do {
                 Move
-            } else if *button == MOUSE_WAIT_DROP_CANCEL {
+            } else if *button == keys.wait_drop_cancel {
                 WaitDrop
             } else {
                 *self
             }
+            Anchor => if *button == keys.measure_do {
+                Measure
+            } else if *button == keys.measure_cancel || *key == keys.measure {
+                Move
+            } else {
+                *self
+            }
+            Measure => if *button == keys.measure_do || *button == keys.measure_cancel || *key == keys.measure {
+                Move
+            } else {
+                *self
+            }
         };
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum Frame {
     Zero,
     Current,
@@ -91,30 +119,230 @@ impl Frame {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Collision {
+    Merge,
+    Bounce,
+}
+
+impl Collision {
+    pub fn next(&mut self) {
+        use Collision::*;
+        *self = match self {
+            Merge => Bounce,
+            Bounce => Merge,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SpeedMode {
+    World,
+    Screen,
+}
+
+impl SpeedMode {
+    pub fn next(&mut self) {
+        use SpeedMode::*;
+        *self = match self {
+            World => Screen,
+            Screen => World,
+        }
+    }
+
+    pub fn speed(&self, offset: &Vector3, scale: &Scale) -> Vector3 {
+        match self {
+            SpeedMode::World => *offset * SPEED_SCALING_FACTOR,
+            SpeedMode::Screen => *offset / scale.time,
+        }
+    }
+}
+
+pub fn parse_method(name: &str) -> Result<Method, Box<dyn Error>> {
+    let name = name.to_lowercase();
+    let first = Method::RungeKutta4;
+    let mut method = first;
+    loop {
+        if format!("{:?}", method).to_lowercase() == name || (name == "rk4" && method == Method::RungeKutta4) {
+            return Ok(method);
+        }
+        method.next();
+        if method == first {
+            break;
+        }
+    }
+    Err(format!("unknown integration method '{}'", name).into())
+}
+
+fn parse_rate(rate_str: &str) -> Result<u64, Box<dyn Error>> {
+    let rate: u64 = rate_str.parse()?;
+    if rate == 0 {
+        return Err(format!("rate must be positive, got {}", rate_str).into());
+    }
+    Ok(rate)
+}
+
+fn parse_spacing(spacing_str: &str) -> Result<f64, Box<dyn Error>> {
+    let spacing: f64 = spacing_str.parse()?;
+    if !spacing.is_finite() || spacing <= 0. {
+        return Err(format!("spacing must be positive, got {}", spacing_str).into());
+    }
+    Ok(spacing)
+}
+
+fn clamp_oversampling(oversampling: u32) -> u32 {
+    min(max(oversampling, MIN_OVERSAMPLING), MAX_OVERSAMPLING)
+}
+
+pub fn random_body<R: Rng>(index: usize, rng: &mut R) -> Body {
+    let (low, high) = RANDOM_MASS_RANGE;
+    let mut body = Body::new();
+    body.name = format!("Body {}", index);
+    body.mass = low * (high / low).powf(rng.gen_range(0., 1.));
+    body.radius = (0.75 * body.mass / (std::f64::consts::PI * RANDOM_DENSITY)).cbrt();
+    body.color = [rng.gen_range(0., 1.), rng.gen_range(0., 1.), rng.gen_range(0., 1.), 1.];
+    body
+}
+
+pub fn parse_palette(name: &str) -> Result<Palette, Box<dyn Error>> {
+    match name.to_lowercase().as_str() {
+        "dark" => Ok(Palette::dark()),
+        "light" => Ok(Palette::light()),
+        _ => Err(format!("unknown palette '{}'", name).into()),
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct ConfigFile {
+    path: Option<String>,
+    keys: Option<String>,
+    size: Option<[f64; 2]>,
+    oversampling: Option<u32>,
+    orientation: Option<[f64; 3]>,
+    trajectory: Option<bool>,
+    trajectory_length: Option<usize>,
+    orbits: Option<bool>,
+    pause: Option<bool>,
+    scale: Option<Scale>,
+    palette: Option<Palette>,
+}
 
 #[derive(Debug)]
 pub struct Config {
     pub path: Option<String>,
+    pub log: Option<String>,
+    pub keys: Option<String>,
     pub size: Size,
     pub scale: Scale,
     pub oversampling: u32,
     pub orientation: Orientation,
     pub trajectory: bool,
+    pub trajectory_length: usize,
+    pub heatmap: bool,
+    pub trajectory_stride: Option<usize>,
     pub orbits: bool,
+    pub spheres: bool,
+    pub potential: bool,
+    pub potential_resolution: f64,
+    pub speeds: bool,
+    pub velocity_scale: f64,
+    pub accelerations: bool,
+    pub hud: bool,
+    pub grid: bool,
+    pub snap: bool,
+    pub snap_spacing: Option<f64>,
+    pub auto_rotate: bool,
+    pub follow: bool,
     pub pause: bool,
+    pub reverse: bool,
+    pub speed_mode: SpeedMode,
+    pub softening: f64,
+    pub gravity: f64,
+    pub drag: bool,
+    pub drag_coefficient: f64,
+    pub spring: bool,
+    pub spring_stiffness: f64,
+    pub halo: bool,
+    pub halo_velocity: f64,
+    pub halo_core_radius: f64,
+    pub relativistic: bool,
+    pub coulomb: bool,
+    pub charge_step: f64,
+    pub collision: Collision,
+    pub restitution: f64,
+    pub auto_remove: bool,
+    pub away_sigma: f64,
+    pub barnes_hut_theta: Option<f64>,
+    pub bodies: Option<usize>,
+    pub method: Method,
+    pub headless: Option<u64>,
+    pub fps: u64,
+    pub ups: u64,
+    pub replay: Option<String>,
+    pub seed: Option<u64>,
+    pub max_time_scale: f64,
+    pub min_steps_per_orbit: f64,
+    pub palette: Option<Palette>,
 }
 
 impl Config {
     pub fn new(path: Option<String>, size: Size, scale: Scale, oversampling: u32) -> Config {
         Config {
             path,
+            log: None,
+            keys: None,
             size,
             scale,
             oversampling,
             orientation: Orientation::new(0., 0., 0.),
             trajectory: false,
+            trajectory_length: DEFAULT_TRAJECTORY_LENGTH,
+            heatmap: false,
+            trajectory_stride: None,
             orbits: true,
+            spheres: false,
+            potential: false,
+            potential_resolution: DEFAULT_POTENTIAL_RESOLUTION,
+            speeds: false,
+            velocity_scale: DEFAULT_VELOCITY_SCALE,
+            accelerations: false,
+            hud: false,
+            grid: false,
+            snap: false,
+            snap_spacing: None,
+            auto_rotate: false,
+            follow: false,
             pause: true,
+            reverse: false,
+            speed_mode: SpeedMode::World,
+            softening: 0.,
+            gravity: G_UNIV,
+            drag: false,
+            drag_coefficient: DEFAULT_DRAG_COEFFICIENT,
+            spring: false,
+            spring_stiffness: DEFAULT_SPRING_STIFFNESS,
+            halo: false,
+            halo_velocity: DEFAULT_HALO_VELOCITY,
+            halo_core_radius: DEFAULT_HALO_CORE_RADIUS,
+            relativistic: false,
+            coulomb: false,
+            charge_step: DEFAULT_CHARGE_STEP,
+            collision: Collision::Merge,
+            restitution: 1.,
+            auto_remove: true,
+            away_sigma: DEFAULT_AWAY_SIGMA,
+            barnes_hut_theta: None,
+            bodies: None,
+            method: Method::RungeKutta4,
+            headless: None,
+            fps: DEFAULT_FPS,
+            ups: DEFAULT_UPS,
+            replay: None,
+            seed: None,
+            max_time_scale: DEFAULT_MAX_TIME_SCALE,
+            min_steps_per_orbit: DEFAULT_MIN_STEPS_PER_ORBIT,
+            palette: None,
         }
     }
 
@@ -126,71 +354,295 @@ impl Config {
         opts.optopt("s", "oversampling", "Sets oversampling", "NUMBER");
         opts.optopt("w", "width", "Sets window width", "NUMBER");
         opts.optopt("h", "height", "Sets window height", "NUMBER");
+        opts.optopt("e", "softening", "Sets gravity softening length in meters", "NUMBER");
+        opts.optopt("g", "gravity", "Sets the gravitational constant in m3/kg/s2", "NUMBER");
+        opts.optopt("l", "log", "Writes logger output to file", "FILEPATH");
+        opts.optopt("", "drag", "Sets the drag coefficient in 1/m", "NUMBER");
+        opts.optopt("", "spring", "Sets the central spring stiffness in 1/s2", "NUMBER");
+        opts.optopt("", "halo-velocity", "Sets the asymptotic circular speed of the halo in m/s", "NUMBER");
+        opts.optopt("", "halo-core", "Sets the core radius of the halo in meters", "NUMBER");
+        opts.optopt("", "charge-step", "Sets the charge added to the current body per key press in coulombs", "NUMBER");
+        opts.optopt("b", "barnes-hut", "Enables Barnes-Hut gravity with the given opening angle", "NUMBER");
+        opts.optopt("n", "bodies", "Spawns the given number of random bodies", "NUMBER");
+        opts.optopt("m", "method", "Sets the initial integration method (rk4, ...)", "NAME");
+        opts.optopt("", "headless", "Runs the given number of steps without window", "STEPS");
+        opts.optopt("", "fps", "Sets the maximum number of frames rendered per second", "NUMBER");
+        opts.optopt("", "ups", "Sets the number of physics updates per second", "NUMBER");
+        opts.optopt("", "replay", "Plays back a recorded simulation", "FILEPATH");
+        opts.optopt("", "seed", "Seeds the random generator for reproducible scenarios", "NUMBER");
+        opts.optopt("", "max-time-scale", "Sets the maximum time scale in s/real s", "NUMBER");
+        opts.optopt("", "min-steps-per-orbit", "Sets the integration steps per orbit below which a warning is logged", "NUMBER");
+        opts.optopt("c", "config", "Loads the configuration from a TOML file", "FILEPATH");
+        opts.optopt("", "snap-spacing", "Sets the spacing of the placement grid in meters", "NUMBER");
+        opts.optopt("", "trajectory-stride", "Draws every given segment of the trajectories", "NUMBER");
+        opts.optopt("", "potential-resolution", "Sets the size of the potential field cells in px", "NUMBER");
+        opts.optopt("", "palette", "Sets the color palette (dark, light)", "NAME");
+        opts.optopt("k", "keys", "Loads the key bindings from a TOML file", "FILEPATH");
         let matches = opts.parse(&args[1..])?;
 
-        let path = matches.opt_str("o");
-        let mut scale = Scale::unit();
-        let mut oversampling: u32 = DEFAULT_OVERSAMPLING;
-        let mut size = Size::from(DEFAULT_WINDOW_SIZE);
+        let mut config = match matches.opt_str("c") {
+            None => Config::default(),
+            Some(config_str) => Config::from_file(Path::new(&config_str))?,
+        };
 
+        if let Some(path_str) = matches.opt_str("o") {
+            config.path = Some(path_str);
+        }
         if let Some(distance_str) = matches.opt_str("d") {
-            scale.distance = distance_str.parse()?;
+            config.scale.distance = distance_str.parse()?;
         }
         if let Some(time_str) = matches.opt_str("t") {
-            scale.time = time_str.parse()?;
+            config.scale.time = time_str.parse()?;
         }
         if let Some(oversampling_str) = matches.opt_str("s") {
-            oversampling = oversampling_str.parse()?;
+            config.oversampling = clamp_oversampling(oversampling_str.parse()?);
         }
         if let Some(width_str) = matches.opt_str("w") {
-            size.width = width_str.parse()?;
+            config.size.width = width_str.parse()?;
         }
         if let Some(height_str) = matches.opt_str("h") {
-            size.height = height_str.parse()?;
+            config.size.height = height_str.parse()?;
+        }
+        if let Some(log_str) = matches.opt_str("l") {
+            config.log = Some(log_str);
+        }
+        if let Some(softening_str) = matches.opt_str("e") {
+            config.softening = softening_str.parse()?;
+        }
+        if let Some(gravity_str) = matches.opt_str("g") {
+            config.gravity = gravity_str.parse()?;
+        }
+        if let Some(drag_str) = matches.opt_str("drag") {
+            config.drag_coefficient = drag_str.parse()?;
+        }
+        if let Some(spring_str) = matches.opt_str("spring") {
+            config.spring_stiffness = spring_str.parse()?;
+        }
+        if let Some(velocity_str) = matches.opt_str("halo-velocity") {
+            config.halo_velocity = velocity_str.parse()?;
+        }
+        if let Some(core_str) = matches.opt_str("halo-core") {
+            config.halo_core_radius = core_str.parse()?;
+        }
+        if let Some(charge_str) = matches.opt_str("charge-step") {
+            config.charge_step = charge_str.parse()?;
+        }
+        if let Some(theta_str) = matches.opt_str("b") {
+            config.barnes_hut_theta = Some(theta_str.parse()?);
+        }
+        if let Some(bodies_str) = matches.opt_str("n") {
+            config.bodies = Some(bodies_str.parse()?);
+        }
+        if let Some(method_str) = matches.opt_str("m") {
+            config.method = parse_method(&method_str)?;
+        }
+        if let Some(keys_str) = matches.opt_str("k") {
+            config.keys = Some(keys_str);
+        }
+        if let Some(headless_str) = matches.opt_str("headless") {
+            config.headless = Some(headless_str.parse()?);
+        }
+        if let Some(fps_str) = matches.opt_str("fps") {
+            config.fps = parse_rate(&fps_str)?;
+        }
+        if let Some(ups_str) = matches.opt_str("ups") {
+            config.ups = parse_rate(&ups_str)?;
+        }
+        if let Some(replay_str) = matches.opt_str("replay") {
+            config.replay = Some(replay_str);
+        }
+        if let Some(seed_str) = matches.opt_str("seed") {
+            config.seed = Some(seed_str.parse()?);
+        }
+        if let Some(max_str) = matches.opt_str("max-time-scale") {
+            config.max_time_scale = max_str.parse()?;
+        }
+        if let Some(steps_str) = matches.opt_str("min-steps-per-orbit") {
+            config.min_steps_per_orbit = steps_str.parse()?;
+        }
+        if let Some(spacing_str) = matches.opt_str("snap-spacing") {
+            config.snap_spacing = Some(parse_spacing(&spacing_str)?);
+        }
+        if let Some(stride_str) = matches.opt_str("trajectory-stride") {
+            config.trajectory_stride = Some(max(stride_str.parse()?, 1));
+        }
+        if let Some(resolution_str) = matches.opt_str("potential-resolution") {
+            config.potential_resolution = resolution_str.parse::<f64>()?.max(1.);
+        }
+        if let Some(palette_str) = matches.opt_str("palette") {
+            config.palette = Some(parse_palette(&palette_str)?);
+        }
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read config file {}: {}", path.display(), err))?;
+        let file: ConfigFile = toml::from_str(&content)
+            .map_err(|err| format!("invalid config file {}: {}", path.display(), err))?;
+        let mut config = Config::default();
+        config.path = file.path;
+        config.keys = file.keys;
+        if let Some(size) = file.size {
+            config.size = Size::from(size);
+        }
+        if let Some(scale) = file.scale {
+            config.scale = scale;
+        }
+        if let Some(oversampling) = file.oversampling {
+            config.oversampling = clamp_oversampling(oversampling);
+        }
+        if let Some(orientation) = file.orientation {
+            config.orientation = Orientation::new(orientation[0], orientation[1], orientation[2]);
+        }
+        if let Some(trajectory) = file.trajectory {
+            config.trajectory = trajectory;
+        }
+        if let Some(trajectory_length) = file.trajectory_length {
+            config.trajectory_length = max(trajectory_length, MIN_TRAJECTORY_LENGTH);
+        }
+        if let Some(orbits) = file.orbits {
+            config.orbits = orbits;
+        }
+        if let Some(pause) = file.pause {
+            config.pause = pause;
+        }
+        config.palette = file.palette;
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = ConfigFile {
+            path: self.path.clone(),
+            keys: self.keys.clone(),
+            size: Some([self.size.width, self.size.height]),
+            oversampling: Some(self.oversampling),
+            orientation: Some(self.orientation.angles()),
+            trajectory: Some(self.trajectory),
+            trajectory_length: Some(self.trajectory_length),
+            orbits: Some(self.orbits),
+            pause: Some(self.pause),
+            scale: Some(self.scale),
+            palette: self.palette.clone(),
+        };
+        fs::write(path, toml::to_string(&file)?)?;
+        Ok(())
+    }
+
+    pub fn background(&self) -> [f32; 4] {
+        match &self.palette {
+            None => BLACK,
+            Some(palette) => palette.background,
+        }
+    }
+
+    pub fn field(&self) -> Field {
+        Field {
+            gravity: self.gravity,
+            drag: if self.drag { Some(self.drag_coefficient) } else { None },
+            spring: if self.spring { Some(self.spring_stiffness) } else { None },
+            halo: if self.halo {
+                Some(Halo { velocity: self.halo_velocity, core_radius: self.halo_core_radius })
+            } else {
+                None
+            },
+            relativistic: self.relativistic,
+            coulomb: self.coulomb,
         }
-        Ok(Config::new(path, size, scale, oversampling))
     }
 
     pub fn default() -> Config {
         Config::new(None, Size::from(DEFAULT_WINDOW_SIZE), Scale::unit(), DEFAULT_OVERSAMPLING)
     }
 
-    pub fn update(&mut self, key: &Key) {
-        if *key == KEY_TOGGLE_TRAJECTORY {
+    pub fn update(&mut self, key: &Key, keys: &KeyMap) {
+        if *key == keys.toggle_trajectory {
             self.trajectory = !self.trajectory;
-        } else if *key == KEY_TOGGLE_PAUSE {
+        } else if *key == keys.toggle_pause {
             self.pause = !self.pause;
-        } else if *key == KEY_TOGGLE_ORBITS {
+        } else if *key == keys.toggle_reverse {
+            self.reverse = !self.reverse;
+        } else if *key == keys.toggle_orbits {
             self.orbits = !self.orbits;
-        } else if *key == KEY_INCREASE_OVERSAMPLING {
+        } else if *key == keys.toggle_speeds {
+            self.speeds = !self.speeds;
+        } else if *key == keys.toggle_accelerations {
+            self.accelerations = !self.accelerations;
+        } else if *key == keys.toggle_hud {
+            self.hud = !self.hud;
+        } else if *key == keys.toggle_heatmap {
+            self.heatmap = !self.heatmap;
+        } else if *key == keys.toggle_potential {
+            self.potential = !self.potential;
+        } else if *key == keys.toggle_spheres {
+            self.spheres = !self.spheres;
+        } else if *key == keys.toggle_grid {
+            self.grid = !self.grid;
+        } else if *key == keys.toggle_snap {
+            self.snap = !self.snap;
+        } else if *key == keys.toggle_auto_rotate {
+            self.auto_rotate = !self.auto_rotate;
+        } else if *key == keys.toggle_follow {
+            self.follow = !self.follow;
+        } else if *key == keys.toggle_speed_mode {
+            self.speed_mode.next();
+        } else if *key == keys.toggle_drag {
+            self.drag = !self.drag;
+        } else if *key == keys.toggle_spring {
+            self.spring = !self.spring;
+        } else if *key == keys.toggle_halo {
+            self.halo = !self.halo;
+        } else if *key == keys.toggle_relativistic {
+            self.relativistic = !self.relativistic;
+        } else if *key == keys.toggle_coulomb {
+            self.coulomb = !self.coulomb;
+        } else if *key == keys.toggle_auto_remove {
+            self.auto_remove = !self.auto_remove;
+        } else if *key == keys.next_collision_state {
+            self.collision.next();
+        } else if *key == keys.increase_oversampling {
             self.increase_oversampling();
-        } else if *key == KEY_DECREASE_OVERSAMPLING {
+        } else if *key == keys.decrease_oversampling {
             self.decrease_oversampling();
-        } else if *key == KEY_ROTATION_DOWN {
+        } else if *key == keys.increase_trajectory {
+            self.increase_trajectory_length();
+        } else if *key == keys.decrease_trajectory {
+            self.decrease_trajectory_length();
+        } else if *key == keys.reset_orientation {
+            self.orientation.reset();
+        } else if *key == keys.rotation_down {
             self.orientation.increment_x();
-        } else if *key == KEY_ROTATION_UP {
+        } else if *key == keys.rotation_up {
             self.orientation.decrement_x();
-        } else if *key == KEY_ROTATION_LEFT {
+        } else if *key == keys.rotation_left {
             self.orientation.increment_z();
-        } else if *key == KEY_ROTATION_RIGHT {
+        } else if *key == keys.rotation_right {
             self.orientation.decrement_z();
-        } else if *key == KEY_INCREASE_DISTANCE {
+        } else if *key == keys.increase_distance {
             self.scale.increase_distance();
-        } else if *key == KEY_DECREASE_DISTANCE {
+        } else if *key == keys.decrease_distance {
             self.scale.decrease_distance();
-        } else if *key == KEY_INCREASE_TIME {
-            self.scale.increase_time();
-        } else if *key == KEY_DECREASE_TIME {
+        } else if *key == keys.increase_time {
+            self.scale.increase_time(self.max_time_scale);
+        } else if *key == keys.decrease_time {
             self.scale.decrease_time();
         }
     }
 
     fn increase_oversampling(&mut self) {
-        self.oversampling = min(self.oversampling << 1, std::u32::MAX);
+        self.oversampling = min(self.oversampling.saturating_mul(2), MAX_OVERSAMPLING);
     }
 
     fn decrease_oversampling(&mut self) {
-        self.oversampling = max(self.oversampling >> 1, std::u32::MIN + 1);
+        self.oversampling = max(self.oversampling >> 1, MIN_OVERSAMPLING);
+    }
+
+    fn increase_trajectory_length(&mut self) {
+        self.trajectory_length = min(self.trajectory_length << 1, MAX_TRAJECTORY_LENGTH);
+    }
+
+    fn decrease_trajectory_length(&mut self) {
+        self.trajectory_length = max(self.trajectory_length >> 1, MIN_TRAJECTORY_LENGTH);
     }
 }
 
@@ -199,6 +651,8 @@ pub struct Status {
     pub direction: Direction,
     pub reset_circles: bool,
     pub update_transform: bool,
+    pub single_step: bool,
+    pub frame: u64,
     pub state: State,
     pub step: Step,
 }
@@ -209,6 +663,8 @@ impl Status {
             direction: Direction::Hold,
             reset_circles: true,
             update_transform: true,
+            single_step: false,
+            frame: 0,
             state: State::Reset,
             step: Step::new(),
         }
@@ -218,33 +674,96 @@ impl Status {
         self.state == State::WaitSpeed || self.state == State::WaitDrop
     }
 
-    pub fn update(&mut self, key: &Option<Key>, button: &Option<MouseButton>) {
+    pub fn update(&mut self, key: &Option<Key>, button: &Option<MouseButton>, keys: &KeyMap) {
         match key {
             None => {
                 self.direction = HOLD;
                 match button {
-                    None => self.state.next(&KEY_UNKNOWN, &BUTTON_UNKNOWN),
-                    Some(button) => self.state.next(&KEY_UNKNOWN, button),
+                    None => self.state.next(&KEY_UNKNOWN, &BUTTON_UNKNOWN, keys),
+                    Some(button) => self.state.next(&KEY_UNKNOWN, button, keys),
                 };
             }
             Some(key) => {
                 self.reset_circles = true;
                 self.update_transform = true;
-                self.direction = Direction::from(*key);
+                self.direction = Direction::from_key(key, keys);
+                self.single_step = *key == keys.step;
                 match button {
-                    None => self.state.next(key, &BUTTON_UNKNOWN),
-                    Some(button) => self.state.next(key, button),
+                    None => self.state.next(key, &BUTTON_UNKNOWN, keys),
+                    Some(button) => self.state.next(key, button, keys),
                 };
             }
         };
     }
 
-    pub fn clear(&mut self) {
-        self.state.next(&KEY_UNKNOWN, &BUTTON_UNKNOWN);
-        self.direction = Direction::from(KEY_UNKNOWN);
+    pub fn clear(&mut self, keys: &KeyMap) {
+        self.state.next(&KEY_UNKNOWN, &BUTTON_UNKNOWN, keys);
+        self.direction = HOLD;
         self.reset_circles = false;
         self.update_transform = false;
+        self.single_step = false;
+        self.frame += 1;
+    }
+}
+
+#[derive(Clone)]
+pub enum Edit {
+    Add(usize),
+    Remove(usize, Point3, Body, f64),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Approach {
+    pub first: usize,
+    pub second: usize,
+    pub distance: f64,
+    pub minimum: f64,
+    pub elapsed: f64,
+    pub time: f64,
+}
+
+impl Approach {
+    pub fn new(first: usize, second: usize, distance: f64) -> Approach {
+        Approach {
+            first,
+            second,
+            distance,
+            minimum: distance,
+            elapsed: 0.,
+            time: 0.,
+        }
     }
+
+    pub fn push(&mut self, distance: f64, dt: f64) {
+        self.distance = distance;
+        self.elapsed += dt;
+        if distance < self.minimum {
+            self.minimum = distance;
+            self.time = self.elapsed;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.minimum = self.distance;
+        self.elapsed = 0.;
+        self.time = 0.;
+    }
+
+    fn involves(&self, i: usize) -> bool {
+        self.first == i || self.second == i
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub positions: Vec<[f64; 3]>,
+    pub speeds: Vec<[f64; 3]>,
+    pub masses: Vec<f64>,
+    pub bodies: Vec<Body>,
+    #[serde(default)]
+    pub charges: Vec<f64>,
+    pub frame: Frame,
+    pub current: usize,
 }
 
 pub struct Simulator {
@@ -254,6 +773,17 @@ pub struct Simulator {
     pub frame: Frame,
     pub solver: Solver,
     pub stats: Statistics,
+    pub energy_history: History,
+    pub momentum_history: History,
+    pub barycenter_start: Option<Vector3>,
+    pub field: Field,
+    pub marked: Option<usize>,
+    pub approach: Option<Approach>,
+    pub accelerations: Vec<Vector3>,
+    edits: Vec<Edit>,
+    previous_positions: Vec<Vector3>,
+    charges: Vec<f64>,
+    initial: Snapshot,
 }
 
 impl From<Cluster> for Simulator {
@@ -267,22 +797,45 @@ impl From<Cluster> for Simulator {
 
 impl Simulator {
     pub fn new(cluster: Cluster, system: orbital::Cluster, solver: Solver) -> Self {
-        Simulator {
+        let mut ret = Simulator {
             cluster,
             system,
             current: 0,
             frame: Frame::Zero,
             solver,
             stats: Statistics::new(),
-        }
+            energy_history: History::new(),
+            momentum_history: History::new(),
+            barycenter_start: None,
+            field: Field::new(G_UNIV),
+            marked: None,
+            approach: None,
+            accelerations: vec![],
+            edits: Vec::with_capacity(UNDO_SIZE),
+            previous_positions: vec![],
+            charges: vec![],
+            initial: Snapshot {
+                positions: vec![],
+                speeds: vec![],
+                masses: vec![],
+                bodies: vec![],
+                charges: vec![],
+                frame: Frame::Zero,
+                current: 0,
+            },
+        };
+        ret.previous_positions = ret.positions();
+        ret.charges = vec![0.; ret.cluster.len()];
+        ret.initial = ret.snapshot();
+        ret
     }
 
     pub fn orbital(system: orbital::Cluster, true_anomalies: Vec<f64>, solver: Solver) -> Self {
         let mut points: Vec<Point3> = Vec::with_capacity(system.bodies.len());
         for i in 0..system.bodies.len() {
             points.push(Point3::inertial(
-                system[i].orbit.position_at(true_anomalies[i]),
-                system[i].orbit.speed_at(true_anomalies[i]),
+                system[i].orbit.inclined_position_at(true_anomalies[i]),
+                system[i].orbit.inclined_speed_at(true_anomalies[i]),
                 system[i].mass,
             ));
         }
@@ -297,9 +850,8 @@ impl Simulator {
         Simulator::orbital(system, true_anomalies, solver)
     }
 
-    pub fn orbital_at_random(system: orbital::Cluster, solver: Solver) -> Self {
+    pub fn orbital_at_random<R: Rng>(system: orbital::Cluster, solver: Solver, rng: &mut R) -> Self {
         let two_pi = 2. * std::f64::consts::PI;
-        let mut rng = rand::thread_rng();
         let mut true_anomalies: Vec<f64> = Vec::with_capacity(system.bodies.len());
         for _ in system.bodies.iter() {
             true_anomalies.push(rng.gen_range(0., two_pi))
@@ -307,6 +859,35 @@ impl Simulator {
         Simulator::orbital(system, true_anomalies, solver)
     }
 
+    pub fn random<R: Rng>(count: usize, radius: f64, gravity: f64, solver: Solver, rng: &mut R) -> Self {
+        let two_pi = 2. * std::f64::consts::PI;
+        let bodies: Vec<Body> = (0..count).map(|i| random_body(i, rng)).collect();
+        let mass: f64 = bodies.iter().map(|body| body.mass).sum();
+        let max_speed = (gravity * mass / radius).sqrt();
+        let mut points: Vec<Point3> = Vec::with_capacity(count);
+        for body in bodies.iter() {
+            let distance = radius * rng.gen_range(0., 1f64).sqrt();
+            let angle = rng.gen_range(0., two_pi);
+            let speed = max_speed * rng.gen_range(0., 1.);
+            let direction = rng.gen_range(0., two_pi);
+            points.push(Point3::inertial(
+                vec3(distance * angle.cos(), distance * angle.sin(), 0.),
+                vec3(speed * direction.cos(), speed * direction.sin(), 0.),
+                body.mass,
+            ));
+        }
+        let mut ret = Simulator::new(Cluster::new(points), orbital::Cluster::from(bodies), solver);
+        ret.field.gravity = gravity;
+        ret.system.update_orbits(&ret.cluster.points, ret.cluster.barycenter());
+        ret.initial = ret.snapshot();
+        ret
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(&self.system.bodies)?)?;
+        Ok(())
+    }
+
     #[inline]
     pub fn current(&self) -> Option<&Point3> { self.cluster.points.get(self.current) }
 
@@ -316,6 +897,14 @@ impl Simulator {
     #[inline]
     pub fn current_index(&self) -> usize { self.current }
 
+    #[inline]
+    pub fn set_current(&mut self, index: usize) -> &mut Self {
+        if index < self.cluster.len() {
+            self.current = index;
+        }
+        self
+    }
+
     #[inline]
     pub fn last(&self) -> Option<&Point3> { self.cluster.points.last() }
 
@@ -344,22 +933,22 @@ impl Simulator {
         Some(self.current)
     }
 
-    pub fn update(&mut self, key: &Option<Key>, bypass_last: bool) -> &mut Self {
+    pub fn update(&mut self, key: &Option<Key>, bypass_last: bool, keys: &KeyMap) -> &mut Self {
         if let Some(key) = key {
-            if *key == KEY_NEXT_METHOD_STATE {
+            if *key == keys.next_method_state {
                 self.solver.method.next();
-            } else if *key == KEY_NEXT_FRAME_STATE {
+            } else if *key == keys.next_frame_state {
                 self.frame.next();
-            } else if *key == KEY_INCREASE_CURRENT_INDEX {
+            } else if *key == keys.increase_current_index {
                 self.increment_current(bypass_last);
-            } else if *key == KEY_DECREASE_CURRENT_INDEX {
+            } else if *key == keys.decrease_current_index {
                 self.decrement_current();
             }
         }
         self
     }
 
-    pub fn remove_aways(&mut self) -> Option<usize> {
+    pub fn remove_aways(&mut self, sigma: f64) -> Option<usize> {
         self.stats.update(&self.cluster, None);
         let max_distance = self.stats.max_distance;
         let max_index = self.stats.max_index;
@@ -367,27 +956,179 @@ impl Simulator {
             return None;
         }
         self.stats.update(&self.cluster, Some(self.stats.max_index));
-        if max_distance > self.stats.mean + 10e2 * self.stats.deviation {
-            self.remove(max_index);
+        if max_distance > self.stats.mean + sigma * self.stats.deviation {
+            self.take(max_index);
+            self.edits.clear();
             return Some(max_index);
         }
         return None;
     }
 
+    pub fn remove_diverged(&mut self) -> Vec<(usize, Body)> {
+        let mut removed = Vec::new();
+        let mut i = 0;
+        while i < self.cluster.len() {
+            let state = &self.cluster[i].state;
+            let finite = [state.position, state.speed].iter()
+                .all(|vector| vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite());
+            if finite {
+                i += 1;
+                continue;
+            }
+            removed.push((i, self.system.bodies[i].clone()));
+            self.take(i);
+        }
+        if !removed.is_empty() {
+            self.edits.clear();
+        }
+        removed
+    }
+
+    pub fn merge_collisions(&mut self, radii: &Vec<f64>) -> Vec<usize> {
+        let mut radii = radii.clone();
+        let mut merged = Vec::new();
+        let mut i = 0;
+        while i < self.cluster.len() {
+            let mut j = i + 1;
+            let mut removed = false;
+            while j < self.cluster.len() {
+                if self.contact(i, j, radii[i] + radii[j]).is_none() {
+                    j += 1;
+                    continue;
+                }
+                let index = self.merge(i, j);
+                let keep = if index == i { j } else { i };
+                radii[keep] = (radii[i].powi(3) + radii[j].powi(3)).cbrt();
+                radii.remove(index);
+                merged.push(index);
+                if index == i {
+                    removed = true;
+                    break;
+                }
+            }
+            if !removed {
+                i += 1;
+            }
+        }
+        merged
+    }
+
+    pub fn resolve_elastic(&mut self, radii: &Vec<f64>, restitution: f64) -> &mut Self {
+        let len = self.cluster.len();
+        for i in 0..len {
+            for j in (i + 1)..len {
+                let separation = match self.contact(i, j, radii[i] + radii[j]) {
+                    None => continue,
+                    Some(separation) => separation,
+                };
+                let distance = separation.magnitude();
+                if distance == 0. {
+                    continue;
+                }
+                let normal = separation / distance;
+                let relative = self.cluster[i].state.speed - self.cluster[j].state.speed;
+                let approach = relative.x * normal.x + relative.y * normal.y + relative.z * normal.z;
+                if approach <= 0. {
+                    continue;
+                }
+                let mass_i = self.cluster[i].mass;
+                let mass_j = self.cluster[j].mass;
+                let impulse = (1. + restitution) * approach / (1. / mass_i + 1. / mass_j);
+                self.cluster.points[i].state.speed -= normal * (impulse / mass_i);
+                self.cluster.points[j].state.speed += normal * (impulse / mass_j);
+            }
+        }
+        self
+    }
+
+    fn contact(&self, i: usize, j: usize, radius: f64) -> Option<Vector3> {
+        let end = self.cluster[j].state.position - self.cluster[i].state.position;
+        if end.magnitude() <= radius {
+            return Some(end);
+        }
+        let start = self.previous_positions[j] - self.previous_positions[i];
+        let delta = end - start;
+        let a = delta.x * delta.x + delta.y * delta.y + delta.z * delta.z;
+        let b = start.x * delta.x + start.y * delta.y + start.z * delta.z;
+        let c = start.x * start.x + start.y * start.y + start.z * start.z - radius * radius;
+        let discriminant = b * b - a * c;
+        if a == 0. || discriminant < 0. {
+            return None;
+        }
+        let t = (-b - discriminant.sqrt()) / a;
+        if t < 0. || t > 1. {
+            return None;
+        }
+        Some(start + delta * t)
+    }
+
+    fn merge(&mut self, i: usize, j: usize) -> usize {
+        let (keep, gone) = if self.cluster[i].mass >= self.cluster[j].mass { (i, j) } else { (j, i) };
+        let mass_keep = self.cluster[keep].mass;
+        let mass_gone = self.cluster[gone].mass;
+        let mass = mass_keep + mass_gone;
+        let position = (self.cluster[keep].state.position * mass_keep + self.cluster[gone].state.position * mass_gone) / mass;
+        let speed = (self.cluster[keep].state.speed * mass_keep + self.cluster[gone].state.speed * mass_gone) / mass;
+        let point = &mut self.cluster.points[keep];
+        point.mass = mass;
+        point.state.position = position;
+        point.state.speed = speed;
+        self.system.bodies[keep].mass = mass;
+        self.system.bodies[keep].radius = (self.system.bodies[keep].radius.powi(3) + self.system.bodies[gone].radius.powi(3)).cbrt();
+        self.charges[keep] += self.charges[gone];
+        if let Some(approach) = self.approach.as_mut() {
+            if approach.involves(keep) {
+                approach.reset();
+            }
+        }
+        self.take(gone);
+        self.edits.clear();
+        gone
+    }
+
     #[inline]
-    pub fn apply<T>(&mut self, dt: f64, iterations: u32, f: T) -> &mut Self where
-        T: FnMut(&Vec<Point3>, usize) -> Vector6 {
+    pub fn apply<T>(&mut self, dt: f64, iterations: u32, mut f: T) -> Vec<(usize, Body)> where
+        T: FnMut(&Vec<Point3>, &[f64], usize) -> Vector6 {
+        if self.energy_history.initial.is_none() {
+            self.push_history();
+        }
+        if self.barycenter_start.is_none() {
+            self.barycenter_start = Some(self.cluster.barycenter().state.position);
+        }
         self.solver.dt = dt;
-        self.solver.iterations = iterations;
-        self.cluster.apply(&mut self.solver, f);
+        self.solver.iterations = 1;
+        let mut diverged = Vec::new();
+        for _ in 0..iterations {
+            self.previous_positions = self.positions();
+            let charges = &self.charges;
+            self.cluster.apply(&mut self.solver, |points, i| f(points, charges, i));
+            diverged.extend(self.remove_diverged());
+            if self.cluster.is_empty() {
+                return diverged;
+            }
+        }
+        if let Some(approach) = self.approach.as_mut() {
+            let distance = self.cluster[approach.first].state.position % self.cluster[approach.second].state.position;
+            approach.push(distance, dt * iterations as f64);
+        }
         self.system.update_orbits(&self.cluster.points, self.cluster.barycenter());
-        self
+        self.push_history();
+        diverged
+    }
+
+    fn push_history(&mut self) {
+        self.energy_history.push(self.field.total_energy(&self.cluster, &self.charges));
+        self.momentum_history.push(physics::angular_momentum(&self.cluster));
     }
 
     #[inline]
     pub fn push(&mut self, point: Point3, body: Body) -> &mut Self {
+        self.previous_positions.push(point.state.position);
+        self.charges.push(0.);
         self.cluster.push(point);
         self.system.push(body);
+        self.reset_baselines();
+        self.record(Edit::Add(self.cluster.len() - 1));
         self
     }
 
@@ -396,19 +1137,325 @@ impl Simulator {
         if self.current == self.cluster.len() - 1 {
             self.decrement_current();
         }
+        if let Some(Edit::Add(_)) = self.edits.last() {
+            self.edits.pop();
+        }
+        self.shift_marks(self.cluster.len() - 1);
+        self.reset_baselines();
+        self.previous_positions.pop();
+        self.charges.pop();
         self.system.pop();
         self.cluster.pop()
     }
 
     #[inline]
     pub fn remove(&mut self, i: usize) -> Point3 {
+        let body = self.system.bodies[i].clone();
+        let charge = self.charges[i];
+        let point = self.take(i);
+        self.record(Edit::Remove(i, point.clone(), body, charge));
+        point
+    }
+
+    #[inline]
+    pub fn charges(&self) -> &Vec<f64> {
+        &self.charges
+    }
+
+    pub fn set_charge(&mut self, i: usize, charge: f64) -> &mut Self {
+        if i < self.charges.len() && self.charges[i] != charge {
+            self.charges[i] = charge;
+            self.reset_baselines();
+        }
+        self
+    }
+
+    pub fn set_field(&mut self, field: Field) -> &mut Self {
+        if self.field != field {
+            self.field = field;
+            self.energy_history.clear();
+            self.momentum_history.clear();
+        }
+        self
+    }
+
+    pub fn remove_barycenter_drift(&mut self) -> &mut Self {
+        let mass: f64 = self.cluster.points.iter().map(|point| point.mass).sum();
+        if mass == 0. {
+            return self;
+        }
+        let speed = physics::linear_momentum(&self.cluster) / mass;
+        for point in self.cluster.points.iter_mut() {
+            point.state.speed -= speed;
+        }
+        self.reset_baselines();
+        self.system.update_orbits(&self.cluster.points, self.cluster.barycenter());
+        self
+    }
+
+    pub fn place_in_circular_orbit(&mut self, index: usize, around: Option<usize>) -> &mut Self {
+        let around = match around.or_else(|| self.dominant_body(index)) {
+            Some(around) if around != index && around < self.cluster.len() => around,
+            _ => return self,
+        };
+        let radius = self.cluster[index].state.position - self.cluster[around].state.position;
+        let distance = radius.magnitude();
+        if distance == 0. {
+            return self;
+        }
+        let relative = self.cluster[index].state.speed - self.cluster[around].state.speed;
+        let mut tangent = vector::consts::ZEROS_3;
+        for normal in [cross(&radius, &relative), vector::consts::EZ_3, vector::consts::EX_3].iter() {
+            tangent = cross(normal, &radius);
+            if tangent.magnitude() > std::f64::EPSILON * distance {
+                break;
+            }
+        }
+        let speed = (self.field.gravity * self.cluster[around].mass / distance).sqrt();
+        let tangent = tangent / tangent.magnitude();
+        self.cluster.points[index].state.speed = self.cluster[around].state.speed + tangent * speed;
+        self.reset_baselines();
+        self.system.update_orbits(&self.cluster.points, self.cluster.barycenter());
+        self
+    }
+
+    fn dominant_body(&self, index: usize) -> Option<usize> {
+        let position = self.cluster.points.get(index)?.state.position;
+        let mut dominant: Option<(usize, f64)> = None;
+        for i in 0..self.cluster.len() {
+            let distance = self.cluster[i].state.position % position;
+            if i == index || distance == 0. {
+                continue;
+            }
+            let pull = self.cluster[i].mass / (distance * distance);
+            match dominant {
+                Some((_, max)) if max >= pull => {}
+                _ => dominant = Some((i, pull)),
+            }
+        }
+        dominant.map(|(i, _)| i)
+    }
+
+    pub fn primary(&self, index: usize) -> Option<usize> {
+        let primary = self.dominant_body(index)?;
+        if self.cluster[primary].mass < self.cluster[index].mass {
+            return None;
+        }
+        Some(primary)
+    }
+
+    pub fn relative_orbit(&self, index: usize) -> Option<(usize, Orbit)> {
+        let primary = self.primary(index)?;
+        let position = self.cluster[index].state.position - self.cluster[primary].state.position;
+        let speed = self.cluster[index].state.speed - self.cluster[primary].state.speed;
+        let mu = self.field.gravity * self.cluster[primary].mass;
+        Some((primary, Orbit::from_state(&position, &speed, mu)))
+    }
+
+    pub fn duplicate_current(&mut self, separation: f64) -> Option<usize> {
+        if self.cluster.is_empty() {
+            return None;
+        }
+        let mut point = self.cluster[self.current].clone();
+        let body = self.system.bodies[self.current].clone();
+        let distance = point.state.position % self.cluster.barycenter().state.position;
+        let offset = if distance > 0. { distance * DUPLICATE_OFFSET } else { body.radius };
+        point.state.position.x += offset.max(separation * (1. + DUPLICATE_OFFSET));
+        let charge = self.charges[self.current];
+        self.push(point, body);
+        let index = self.cluster.len() - 1;
+        self.charges[index] = charge;
+        Some(index)
+    }
+
+    pub fn undo(&mut self) -> Option<Edit> {
+        let edit = self.edits.pop()?;
+        match &edit {
+            Edit::Add(i) => {
+                if *i >= self.cluster.len() {
+                    return None;
+                }
+                self.take(*i);
+            }
+            Edit::Remove(i, point, body, charge) => {
+                if *i > self.cluster.len() {
+                    return None;
+                }
+                self.previous_positions.insert(*i, point.state.position);
+                self.charges.insert(*i, *charge);
+                self.cluster.points.insert(*i, point.clone());
+                self.system.bodies.insert(*i, body.clone());
+                self.marked = None;
+                self.approach = None;
+                self.reset_baselines();
+            }
+        }
+        Some(edit)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let points = &self.cluster.points;
+        Snapshot {
+            positions: points.iter()
+                .map(|point| [point.state.position.x, point.state.position.y, point.state.position.z])
+                .collect(),
+            speeds: points.iter()
+                .map(|point| [point.state.speed.x, point.state.speed.y, point.state.speed.z])
+                .collect(),
+            masses: points.iter().map(|point| point.mass).collect(),
+            bodies: self.system.bodies.clone(),
+            charges: self.charges.clone(),
+            frame: self.frame,
+            current: self.current,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) -> &mut Self {
+        let mut points: Vec<Point3> = Vec::with_capacity(snapshot.masses.len());
+        for i in 0..snapshot.masses.len() {
+            let position = &snapshot.positions[i];
+            let speed = &snapshot.speeds[i];
+            points.push(Point3::inertial(
+                vec3(position[0], position[1], position[2]),
+                vec3(speed[0], speed[1], speed[2]),
+                snapshot.masses[i],
+            ));
+        }
+        self.cluster = Cluster::new(points);
+        self.system = orbital::Cluster::from(snapshot.bodies.clone());
+        self.frame = snapshot.frame;
+        self.current = if snapshot.current < self.cluster.len() { snapshot.current } else { 0 };
+        self.stats = Statistics::new();
+        self.edits.clear();
+        self.previous_positions = self.positions();
+        self.charges = if snapshot.charges.len() == self.cluster.len() {
+            snapshot.charges.clone()
+        } else {
+            vec![0.; self.cluster.len()]
+        };
+        self.marked = None;
+        self.approach = None;
+        self.reset_baselines();
+        self
+    }
+
+    pub fn reset_position_at(&mut self, i: usize, position: &Vector3) -> &mut Self {
+        self.cluster.reset_position_at(i, position);
+        self.previous_positions[i] = *position;
+        self
+    }
+
+    pub fn reset0_at(&mut self, i: usize) -> &mut Self {
+        self.cluster.reset0_at(i);
+        self.previous_positions[i] = self.cluster[i].state.position;
+        self
+    }
+
+    fn positions(&self) -> Vec<Vector3> {
+        self.cluster.points.iter().map(|point| point.state.position).collect()
+    }
+
+    pub fn restart(&mut self) -> &mut Self {
+        let initial = self.initial.clone();
+        self.restore(&initial)
+    }
+
+    fn take(&mut self, i: usize) -> Point3 {
         if self.current == self.cluster.len() - 1 && i == self.current {
             self.decrement_current();
         }
+        self.reset_baselines();
+        self.previous_positions.remove(i);
+        self.charges.remove(i);
+        self.shift_marks(i);
         self.system.remove(i);
         self.cluster.remove(i)
     }
 
+    pub fn predict(&self, index: usize, duration: f64, epsilon: f64) -> Vec<Vector3> {
+        let mut cluster = Cluster::new(self.cluster.points.clone());
+        let len = cluster.len();
+        let steps = min(max(PREDICTION_BUDGET / (len * len), 1), PREDICTION_STEPS);
+        let mut solver = Solver::new(duration / steps as f64, 1, self.solver.method);
+        let field = self.field;
+        let charges = &self.charges;
+        let mut positions = Vec::with_capacity(steps + 1);
+        positions.push(cluster[index].state.position);
+        for _ in 0..steps {
+            cluster.apply(&mut solver, |points, i| {
+                let acceleration = field.acceleration(&points[i], charges[i], points, charges, epsilon);
+                physics::derivative(&points[i].state.speed, &acceleration)
+            });
+            positions.push(cluster[index].state.position);
+        }
+        positions
+    }
+
+    pub fn mark_current(&mut self) -> &mut Self {
+        if self.cluster.is_empty() {
+            return self;
+        }
+        match self.marked {
+            Some(marked) if marked != self.current && marked < self.cluster.len() => {
+                let distance = self.cluster[marked].state.position % self.cluster[self.current].state.position;
+                self.approach = Some(Approach::new(marked, self.current, distance));
+                self.marked = None;
+            }
+            _ => {
+                self.marked = Some(self.current);
+                self.approach = None;
+            }
+        }
+        self
+    }
+
+    pub fn shortest_period(&self) -> Option<f64> {
+        self.system.bodies.iter()
+            .map(|body| body.orbit.period())
+            .filter(|period| period.is_finite() && *period > 0.)
+            .fold(None, |min, period| match min {
+                Some(min) if min <= period => Some(min),
+                _ => Some(period),
+            })
+    }
+
+    pub fn barycenter_drift(&self) -> f64 {
+        match self.barycenter_start {
+            None => 0.,
+            Some(start) => start % self.cluster.barycenter().state.position,
+        }
+    }
+
+    fn shift_marks(&mut self, removed: usize) {
+        let shift = |i: usize| if i > removed { i - 1 } else { i };
+        self.marked = match self.marked {
+            Some(marked) if marked != removed => Some(shift(marked)),
+            _ => None,
+        };
+        self.approach = match self.approach {
+            Some(approach) if !approach.involves(removed) => Some(Approach {
+                first: shift(approach.first),
+                second: shift(approach.second),
+                ..approach
+            }),
+            _ => None,
+        };
+    }
+
+    fn reset_baselines(&mut self) {
+        self.accelerations.clear();
+        self.energy_history.clear();
+        self.momentum_history.clear();
+        self.barycenter_start = None;
+    }
+
+    fn record(&mut self, edit: Edit) {
+        if self.edits.len() == UNDO_SIZE {
+            self.edits.remove(0);
+        }
+        self.edits.push(edit);
+    }
+
     #[inline]
     fn decrement_current(&mut self) -> &mut Self {
         if self.current > 0 {
@@ -425,4 +1472,56 @@ impl Simulator {
         }
         self
     }
-}
\ No newline at end of file
+}
+
+pub struct ClusterBuilder {
+    points: Vec<Point3>,
+    bodies: Vec<Body>,
+    method: Method,
+}
+
+impl ClusterBuilder {
+    pub fn new() -> ClusterBuilder {
+        ClusterBuilder {
+            points: vec![],
+            bodies: vec![],
+            method: Method::RungeKutta4,
+        }
+    }
+
+    pub fn method(&mut self, method: Method) -> &mut Self {
+        self.method = method;
+        self
+    }
+
+    pub fn add_body(&mut self, mass: f64, position: Vector3, speed: Vector3) -> &mut Self {
+        let mut body = Body::new();
+        body.mass = mass;
+        self.points.push(Point3::inertial(position, speed, mass));
+        self.bodies.push(body);
+        self
+    }
+
+    pub fn add_orbital(&mut self, mu: f64, apoapsis: f64, periapsis: f64, argument: f64) -> &mut Self {
+        let mut body = Body::new();
+        body.orbit.mu = mu;
+        body.orbit.apoapsis = apoapsis;
+        body.orbit.periapsis = periapsis;
+        body.orbit.argument = argument;
+        self.points.push(Point3::inertial(
+            body.orbit.inclined_position_at(0.),
+            body.orbit.inclined_speed_at(0.),
+            body.mass,
+        ));
+        self.bodies.push(body);
+        self
+    }
+
+    pub fn build(&self) -> Simulator {
+        Simulator::new(
+            Cluster::new(self.points.clone()),
+            orbital::Cluster::from(self.bodies.clone()),
+            Solver::new(1., 1, self.method),
+        )
+    }
+}
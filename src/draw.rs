@@ -1,59 +1,187 @@
+use std::cmp::{max, min};
 use std::fmt;
 use std::fmt::Debug;
+use std::f64::consts::PI;
+use std::ops::Index;
 
 use dynamics::orbital;
-use dynamics::orbital::Orbit;
+use dynamics::orbital::{Kind, Orbit};
 use geomath::{matrix, vector};
 use geomath::matrix::{Matrix3, Matrix4};
-use geomath::prelude::Algebra;
+use geomath::prelude::{Algebra, Metric};
 use geomath::prelude::transforms::{Rotation3, Similarity};
-use geomath::trajectory::{consts::TRAJECTORY_SIZE, Trajectory3};
-use geomath::vector::{vec3, Vector2, Vector3};
+use geomath::trajectory::consts::TRAJECTORY_SIZE;
+use geomath::vector::{vec2, vec3, Vector2, Vector3};
 use piston::window::Size;
 use piston_window::*;
 use piston_window::context::Context;
-use unitflow::{Rescale, Scale, Serialize, Unit};
+use unitflow::{Compound, Rescale, Scale, Serialize, Unit};
 use unitflow::suffix::*;
 
-use crate::common::{BLACK, BLUE, GREEN, RED, WHITE};
+use crate::common::{BLACK, BLUE, DEFAULT_TRAJECTORY_LENGTH, GREEN, RED, WHITE, YELLOW};
 use crate::common::Orientation;
-use crate::core::Simulator;
+use crate::core::{Config, Frame, Simulator};
+use crate::log::Units;
+use crate::orbit::{Influence, Kepler};
 
 const SCALE_LENGTH: f64 = 50.;
+const ARROW_HEAD_SIZE: f64 = 6.;
+const ACCELERATION_LENGTH: f64 = 5.;
+const ACCELERATION_FLOOR: f64 = 1e-12;
+const HUD_FONT_SIZE: u32 = 14;
+const GRID_SPACING: f64 = 50.;
+const GRID_MAX_LINES: i32 = 64;
+const GRID_ALPHA: f32 = 0.1;
+const INFLUENCE_ALPHA: f32 = 0.3;
+const APSIS_MARKER_SIZE: f64 = 4.;
+const POTENTIAL_ALPHA: f32 = 0.5;
+const TRAJECTORY_STRIDE_BODIES: usize = 16;
+const RADIUS_REFERENCE: f64 = 1e6;
+const RADIUS_LOG_FACTOR: f64 = 2.;
 
-#[derive(Copy, Clone)]
+#[derive(Clone, Debug)]
+pub struct Trail {
+    positions: Vec<Vector3>,
+    speeds: Vec<f64>,
+    start: usize,
+    filled: bool,
+}
+
+impl Trail {
+    pub fn new(length: usize, position: &Vector3) -> Trail {
+        Trail {
+            positions: vec![*position; length],
+            speeds: vec![0.; length],
+            start: 0,
+            filled: true,
+        }
+    }
+
+    pub fn zeros(length: usize) -> Trail {
+        let mut ret = Trail::new(length, &vector::consts::ZEROS_3);
+        ret.filled = false;
+        ret
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    #[inline]
+    pub fn last(&self) -> &Vector3 {
+        &self[self.len() - 1]
+    }
+
+    #[inline]
+    pub fn speed(&self, index: usize) -> f64 {
+        self.speeds[(self.start + index) % self.speeds.len()]
+    }
+
+    #[inline]
+    pub fn is_filled(&self) -> bool {
+        self.filled
+    }
+
+    pub fn push(&mut self, position: &Vector3, speed: f64) -> &mut Self {
+        if !self.filled {
+            self.reset(position);
+        }
+        self.positions[self.start] = *position;
+        self.speeds[self.start] = speed;
+        self.start = (self.start + 1) % self.len();
+        self
+    }
+
+    pub fn reset(&mut self, position: &Vector3) -> &mut Self {
+        for k in 0..self.len() {
+            self.positions[k] = *position;
+            self.speeds[k] = 0.;
+        }
+        self.start = 0;
+        self.filled = true;
+        self
+    }
+
+    pub fn resize(&mut self, length: usize) -> &mut Self {
+        let last = *self.last();
+        let speed = self.speed(self.len() - 1);
+        self.positions = vec![last; length];
+        self.speeds = vec![speed; length];
+        self.start = 0;
+        self
+    }
+
+    pub fn speed_range(&self) -> (f64, f64) {
+        self.speeds.iter().fold((std::f64::MAX, 0.), |(min, max), &speed| (min.min(speed), max.max(speed)))
+    }
+}
+
+impl Index<usize> for Trail {
+    type Output = Vector3;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Vector3 {
+        &self.positions[(self.start + index) % self.positions.len()]
+    }
+}
+
+#[derive(Clone)]
 pub struct Circle {
-    pub trajectory: Trajectory3,
+    pub trajectory: Trail,
+    pub history: Trail,
     pub color: [f32; 4],
     pub radius: f64,
     pub rect: [f64; 4],
+    pub show_trajectory: bool,
 }
 
 impl Circle {
-    pub fn new(trajectory: Trajectory3, radius: f64, color: [f32; 4]) -> Circle {
+    pub fn new(trajectory: Trail, radius: f64, color: [f32; 4]) -> Circle {
         Circle {
+            history: Trail::zeros(trajectory.len()),
             trajectory,
             color,
             radius,
             rect: [0.; 4],
+            show_trajectory: true,
         }
     }
 
     pub fn centered(radius: f64, color: [f32; 4]) -> Circle {
-        Circle::new(Trajectory3::from(vector::consts::ZEROS_3), radius, color)
+        Circle::new(Trail::zeros(DEFAULT_TRAJECTORY_LENGTH), radius, color)
     }
 
     #[inline]
-    pub fn reset(&mut self, trajectory: &Trajectory3, origin: &Trajectory3, transform: &Matrix4) -> &mut Self {
-        for i in 0..TRAJECTORY_SIZE {
-            self.trajectory[i] = *transform * (trajectory[i] - origin[i]);
+    pub fn reset(&mut self, origin: Option<&Trail>, transform: &Matrix4) -> &mut Self {
+        if !self.history.is_filled() {
+            return self;
+        }
+        let length = self.trajectory.len();
+        let start = self.trajectory.start;
+        self.trajectory.speeds.rotate_left(start);
+        self.trajectory.start = 0;
+        self.trajectory.filled = true;
+        for k in 0..length {
+            let position = self.history[k];
+            self.trajectory.positions[k] = *transform * match origin {
+                Some(origin) => position - origin[(k + origin.len()).saturating_sub(length)],
+                None => position,
+            };
         }
         self
     }
 
     #[inline]
-    pub fn update(&mut self, position: &Vector3, origin: &Vector3, transform: &Matrix4) -> &mut Self {
-        self.trajectory.push(&(*transform * (*position - *origin)));
+    pub fn update(&mut self, position: &Vector3, origin: &Vector3, speed: f64, transform: &Matrix4) -> &mut Self {
+        self.history.push(position, speed);
+        self.trajectory.push(&(*transform * (*position - *origin)), speed);
+        self
+    }
+
+    pub fn resize(&mut self, length: usize) -> &mut Self {
+        self.trajectory.resize(length);
+        self.history.resize(length);
         self
     }
 
@@ -76,9 +204,14 @@ impl Debug for Circle {
 
 pub struct Drawer {
     pub circles: Vec<Circle>,
+    barycenter: Trail,
+    pub foreground: [f32; 4],
+    pub pan: Vector2,
     buffer_offset: Vector2,
     buffer_color: [f32; 4],
     distance_unit: Unit,
+    speed_unit: Compound,
+    mass_unit: Unit,
     unit_x: Vector3,
     unit_y: Vector3,
     unit_z: Vector3,
@@ -94,9 +227,14 @@ impl Drawer {
             .collect();
         let mut ret = Drawer {
             circles,
+            barycenter: Trail::zeros(DEFAULT_TRAJECTORY_LENGTH),
+            pan: vector::consts::ZEROS_2,
             buffer_offset: vector::consts::ZEROS_2,
             buffer_color: BLACK,
+            foreground: WHITE,
             distance_unit: Unit::from(Scale::from(Distance::Meter)),
+            speed_unit: Units::default().speed,
+            mass_unit: Units::default().mass,
             unit_x: vector::consts::EX_3,
             unit_y: vector::consts::EY_3,
             unit_z: vector::consts::EZ_3,
@@ -111,14 +249,14 @@ impl Drawer {
     pub fn set_appearance(&mut self, cluster: &orbital::Cluster) -> &mut Self {
         for i in 0..self.circles.len() {
             self.circles[i].color = cluster.bodies[i].color;
-            self.circles[i].radius = cluster.bodies[i].kind.scaled_radius(cluster.bodies[i].radius);
+            self.circles[i].radius = scaled_radius(&cluster.bodies[i].kind, cluster.bodies[i].radius);
         }
         self
     }
 
     pub fn update_transform(&mut self, orientation: &Orientation, scale: f64, size: &Size) -> &mut Self {
         let scale_distance = SCALE_LENGTH / scale;
-        let middle = vec3(size.width * 0.5, size.height * 0.5, 0.);
+        let middle = vec3(size.width * 0.5 + self.pan.x, size.height * 0.5 + self.pan.y, 0.);
         let rotation = Matrix3::from_rotation_x(std::f64::consts::PI) * orientation.rotation();
         self.transform.set_similarity(scale, &rotation, &middle);
         self.inverse_transform = self.transform.inverse();
@@ -128,23 +266,92 @@ impl Drawer {
         self
     }
 
+    pub fn zoom_at(&mut self, cursor: &[f64; 2], factor: f64, size: &Size) -> &mut Self {
+        let x = cursor[0] - size.width * 0.5;
+        let y = cursor[1] - size.height * 0.5;
+        self.pan.x = x - (x - self.pan.x) * factor;
+        self.pan.y = y - (y - self.pan.y) * factor;
+        self
+    }
+
     pub fn update_circles(&mut self, simulator: &Simulator) -> &mut Self {
+        if !simulator.cluster.is_empty() {
+            self.barycenter.push(&simulator.cluster.barycenter().state.position, 0.);
+        }
         for i in 0..self.circles.len() {
             self.circles[i].update(
                 &simulator.cluster[i].state.position,
                 &simulator.origin().position,
+                (simulator.cluster[i].state.speed - simulator.origin().speed).magnitude(),
                 &self.transform,
             );
         }
         self
     }
 
-    pub fn reset_circles(&mut self, simulator: &Simulator) -> &mut Self {
+    pub fn project_positions(&self, simulator: &Simulator) -> Vec<Vector2> {
+        let origin = simulator.origin().position;
+        simulator.cluster.points.iter()
+            .map(|point| {
+                let position = self.transform * (point.state.position - origin);
+                vec2(position.x, position.y)
+            })
+            .collect()
+    }
+
+    pub fn svg(&self, simulator: &Simulator, config: &Config) -> String {
+        let mut ret = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+            w = config.size.width,
+            h = config.size.height,
+        );
+        ret += &format!("<rect width=\"100%\" height=\"100%\" {}/>\n", svg_paint("fill", &config.background()));
+        for i in 0..self.circles.len() {
+            if config.trajectory && self.circles[i].show_trajectory {
+                let points: Vec<Vector3> = (0..self.circles[i].trajectory.len())
+                    .map(|k| self.circles[i].trajectory[k])
+                    .collect();
+                ret += &svg_polyline(&points, &self.circles[i].color);
+            }
+            if config.orbits {
+                ret += &svg_polyline(&self.orbit_points(simulator, i), &self.circles[i].color);
+            }
+        }
+        let positions = self.project_positions(simulator);
         for i in 0..self.circles.len() {
-            self.circles[i].reset(
-                &simulator.cluster[i].state.trajectory,
-                &simulator.origin().trajectory,
-                &self.transform);
+            ret += &format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>\n",
+                positions[i].x, positions[i].y, self.circles[i].radius, svg_paint("fill", &self.circles[i].color),
+            );
+        }
+        ret += "</svg>\n";
+        ret
+    }
+
+    pub fn resize_circles(&mut self, length: usize) -> &mut Self {
+        for circle in self.circles.iter_mut() {
+            if circle.trajectory.len() != length {
+                circle.resize(length);
+            }
+        }
+        if self.barycenter.len() != length {
+            self.barycenter.resize(length);
+        }
+        self
+    }
+
+    pub fn reset_circles(&mut self, simulator: &Simulator) -> &mut Self {
+        let current = match simulator.origin_index() {
+            Some(index) => self.circles.get(index).map(|circle| circle.history.clone()),
+            None => None,
+        };
+        let origin = match simulator.frame {
+            Frame::Zero => None,
+            Frame::Current => current.as_ref(),
+            Frame::Barycenter => Some(&self.barycenter),
+        };
+        for circle in self.circles.iter_mut() {
+            circle.reset(origin, &self.transform);
         }
         self
     }
@@ -156,14 +363,14 @@ impl Drawer {
         self.distance_unit.rescale(&scale_distance);
 
         piston_window::line_from_to(
-            WHITE,
+            self.foreground,
             3.,
             [self.buffer_offset.x, self.buffer_offset.y],
             [self.buffer_offset.x + SCALE_LENGTH, self.buffer_offset.y],
             c.transform, g,
         );
 
-        piston_window::text::Text::new_color(WHITE, 16).draw(
+        piston_window::text::Text::new_color(self.foreground, 16).draw(
             format!("{}", self.distance_unit.string_of(&scale_distance)).as_str(),
             glyphs,
             &c.draw_state,
@@ -172,9 +379,92 @@ impl Drawer {
         ).unwrap();
     }
 
+    pub fn draw_hud(&mut self, simulator: &Simulator, config: &Config, c: &Context, g: &mut G2d, glyphs: &mut Glyphs) {
+        let lines = [
+            format!("bodies: {}", simulator.cluster.len()),
+            format!("method: {:?}", simulator.solver.method),
+            format!("oversampling: {}", config.oversampling),
+            format!("frame: {:?}", simulator.frame),
+            String::from(if config.pause { "paused" } else { "running" }),
+            String::from(if config.reverse { "backward" } else { "forward" }),
+        ];
+        self.buffer_offset.x = 16.;
+        self.buffer_offset.y = 16. + HUD_FONT_SIZE as f64;
+        for line in lines.iter() {
+            piston_window::text::Text::new_color(self.foreground, HUD_FONT_SIZE).draw(
+                line.as_str(),
+                glyphs,
+                &c.draw_state,
+                c.transform.trans(self.buffer_offset.x, self.buffer_offset.y),
+                g,
+            ).unwrap();
+            self.buffer_offset.y += HUD_FONT_SIZE as f64 + 4.;
+        }
+    }
+
+    pub fn draw_potential_field(&mut self, simulator: &Simulator, resolution: f64, softening: f64, size: &Size, c: &Context, g: &mut G2d) {
+        let columns = (size.width / resolution).ceil() as usize;
+        let rows = (size.height / resolution).ceil() as usize;
+        let mut values = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let center = vec3((column as f64 + 0.5) * resolution, (row as f64 + 0.5) * resolution, 0.);
+                let position = self.inverse_transform * center + simulator.origin().position;
+                let potential = simulator.field.potential_at(&position, &simulator.cluster.points, softening);
+                values.push(potential.abs().ln());
+            }
+        }
+        let (min, max) = values.iter()
+            .filter(|value| value.is_finite())
+            .fold((std::f64::MAX, std::f64::MIN), |(min, max), &value| (min.min(value), max.max(value)));
+        if !(max > min) {
+            return;
+        }
+        for row in 0..rows {
+            for column in 0..columns {
+                let value = values[row * columns + column];
+                if !value.is_finite() {
+                    continue;
+                }
+                self.buffer_color = self.foreground;
+                self.buffer_color[3] = POTENTIAL_ALPHA * ((value - min) / (max - min)) as f32;
+                piston_window::rectangle(
+                    self.buffer_color,
+                    [column as f64 * resolution, row as f64 * resolution, resolution, resolution],
+                    c.transform, g,
+                );
+            }
+        }
+    }
+
+    pub fn draw_grid(&mut self, scale: f64, size: &Size, c: &Context, g: &mut G2d) {
+        let mut spacing = grid_spacing(scale);
+        let extent = (size.width * size.width + size.height * size.height).sqrt() / scale;
+        while extent / spacing > GRID_MAX_LINES as f64 {
+            spacing *= 10.;
+        }
+        let count = (extent / spacing).ceil() as i32;
+        let center = self.inverse_transform * vec3(size.width * 0.5, size.height * 0.5, 0.);
+        let x0 = (center.x / spacing).round() * spacing;
+        let y0 = (center.y / spacing).round() * spacing;
+        let half = count as f64 * spacing;
+        let color = [self.foreground[0], self.foreground[1], self.foreground[2], GRID_ALPHA];
+        let mut from;
+        let mut to;
+        for k in -count..=count {
+            let offset = k as f64 * spacing;
+            from = self.transform * vec3(x0 + offset, y0 - half, 0.);
+            to = self.transform * vec3(x0 + offset, y0 + half, 0.);
+            piston_window::line_from_to(color, 1., [from.x, from.y], [to.x, to.y], c.transform, g);
+            from = self.transform * vec3(x0 - half, y0 + offset, 0.);
+            to = self.transform * vec3(x0 + half, y0 + offset, 0.);
+            piston_window::line_from_to(color, 1., [from.x, from.y], [to.x, to.y], c.transform, g);
+        }
+    }
+
     pub fn draw_basis(&mut self, size: &Size, c: &Context, g: &mut G2d) {
-        self.buffer_offset.x = size.width * 0.5;
-        self.buffer_offset.y = size.height * 0.5;
+        self.buffer_offset.x = size.width * 0.5 + self.pan.x;
+        self.buffer_offset.y = size.height * 0.5 + self.pan.y;
 
         piston_window::line_from_to(
             RED,
@@ -223,14 +513,30 @@ impl Drawer {
         }
     }
 
-    pub fn draw_trajectories(&mut self, c: &Context, g: &mut G2d) {
+    pub fn draw_trajectories(&mut self, heatmap: bool, stride: Option<usize>, c: &Context, g: &mut G2d) {
         let mut from;
         let mut to;
+        let stride = max(stride.unwrap_or_else(|| self.trajectory_stride()), 1);
+        let (min_speed, max_speed) = self.circles.iter()
+            .map(|circle| circle.trajectory.speed_range())
+            .fold((std::f64::MAX, 0.), |(min, max): (f64, f64), (low, high)| (min.min(low), max.max(high)));
+        let speed_range = max_speed - min_speed;
         for i in 0..self.circles.len() {
+            if !self.circles[i].show_trajectory {
+                continue;
+            }
             self.buffer_color = self.circles[i].color;
-            for k in 1..TRAJECTORY_SIZE {
-                from = &self.circles[i].trajectory[k - 1];
+            let length = self.circles[i].trajectory.len();
+            let mut k = 0;
+            while k + 1 < length {
+                let previous = k;
+                k = min(k + stride, length - 1);
+                from = &self.circles[i].trajectory[previous];
                 to = &self.circles[i].trajectory[k];
+                if heatmap && speed_range > 0. {
+                    self.buffer_color = heat_color(((self.circles[i].trajectory.speed(k) - min_speed) / speed_range) as f32);
+                }
+                self.buffer_color[3] = self.circles[i].color[3] * k as f32 / (length - 1) as f32;
                 piston_window::line_from_to(
                     self.buffer_color,
                     2.5,
@@ -242,33 +548,205 @@ impl Drawer {
         }
     }
 
-    pub fn draw_orbits(&mut self, simulator: &Simulator, c: &Context, g: &mut G2d) {
-        let mut from;
-        let mut to;
-        let mut angle;
-        let d_angle = 2. * std::f64::consts::PI / TRAJECTORY_SIZE as f64;
+    fn trajectory_stride(&self) -> usize {
+        (self.circles.len() + TRAJECTORY_STRIDE_BODIES - 1) / TRAJECTORY_STRIDE_BODIES
+    }
+
+    pub fn orbit_points(&self, simulator: &Simulator, i: usize) -> Vec<Vector3> {
         let origin = match simulator.origin_index() {
             None => Orbit::zeros(),
             Some(index) => simulator.system[index].orbit,
         };
+        let (start, end) = simulator.system[i].orbit.anomaly_range();
+        let d_angle = (end - start) / TRAJECTORY_SIZE as f64;
+        (0..=TRAJECTORY_SIZE)
+            .map(|k| {
+                let angle = start + k as f64 * d_angle;
+                self.transform * (simulator.system[i].orbit.inclined_position_at(angle) - origin.inclined_position_at(angle))
+            })
+            .collect()
+    }
+
+    pub fn draw_orbits(&mut self, simulator: &Simulator, c: &Context, g: &mut G2d) {
         for i in 0..self.circles.len() {
-            angle = 0.;
-            for _ in 0..TRAJECTORY_SIZE {
-                from = self.transform * (simulator.system[i].orbit.position_at(angle) - origin.position_at(angle));
-                to = self.transform * (simulator.system[i].orbit.position_at(angle + d_angle) - origin.position_at(angle + d_angle));
-                angle += d_angle;
+            let points = self.orbit_points(simulator, i);
+            for k in 1..points.len() {
                 piston_window::line_from_to(
                     self.circles[i].color,
                     2.5,
-                    [from.x, from.y],
-                    [to.x, to.y],
+                    [points[k - 1].x, points[k - 1].y],
+                    [points[k].x, points[k].y],
                     c.transform, g,
                 );
             }
+            self.draw_apsides(simulator, i, c, g);
+        }
+    }
+
+    fn draw_apsides(&mut self, simulator: &Simulator, i: usize, c: &Context, g: &mut G2d) {
+        let orbit = &simulator.system[i].orbit;
+        if orbit.is_degenerated() {
+            return;
+        }
+        let origin = match simulator.origin_index() {
+            None => Orbit::zeros(),
+            Some(index) => simulator.system[index].orbit,
+        };
+        let color = self.circles[i].color;
+        let half = APSIS_MARKER_SIZE * 0.5;
+        let periapsis = self.transform * (orbit.inclined_position_at(0.) - origin.inclined_position_at(0.));
+        piston_window::ellipse(
+            color,
+            [periapsis.x - half, periapsis.y - half, APSIS_MARKER_SIZE, APSIS_MARKER_SIZE],
+            c.transform, g,
+        );
+        if orbit.is_hyperbolic() {
+            return;
+        }
+        let apoapsis = self.transform * (orbit.inclined_position_at(PI) - origin.inclined_position_at(PI));
+        piston_window::Ellipse::new_border(color, 1.).draw(
+            [apoapsis.x - half, apoapsis.y - half, APSIS_MARKER_SIZE, APSIS_MARKER_SIZE],
+            &c.draw_state,
+            c.transform, g,
+        );
+    }
+
+    pub fn draw_spheres_of_influence(&mut self, simulator: &Simulator, scale: f64, c: &Context, g: &mut G2d) {
+        let bodies = &simulator.system.bodies;
+        let primary = match (0..bodies.len()).max_by(|&i, &j| bodies[i].mass.partial_cmp(&bodies[j].mass).unwrap_or(std::cmp::Ordering::Equal)) {
+            None => return,
+            Some(index) => index,
+        };
+        let mut center;
+        for i in 0..self.circles.len() {
+            if i == primary {
+                continue;
+            }
+            let radius = bodies[i].sphere_of_influence(bodies[primary].mass) * scale;
+            if radius < 1. {
+                continue;
+            }
+            center = self.circles[i].trajectory.last();
+            self.buffer_color = self.circles[i].color;
+            self.buffer_color[3] *= INFLUENCE_ALPHA;
+            piston_window::Ellipse::new_border(self.buffer_color, 1.).draw(
+                [center.x - radius, center.y - radius, 2. * radius, 2. * radius],
+                &c.draw_state,
+                c.transform, g,
+            );
+        }
+    }
+
+    pub fn draw_speeds(&mut self, simulator: &Simulator, velocity_scale: f64, scale: f64, c: &Context, g: &mut G2d) {
+        let origin = simulator.origin();
+        let mut from;
+        let mut to;
+        for i in 0..self.circles.len() {
+            let state = &simulator.cluster[i].state;
+            from = self.circles[i].trajectory.last();
+            to = self.transform * (state.position - origin.position + (state.speed - origin.speed) * (velocity_scale / scale));
+            piston_window::Line::new(self.circles[i].color, 1.).draw_arrow(
+                [from.x, from.y, to.x, to.y],
+                ARROW_HEAD_SIZE,
+                &c.draw_state,
+                c.transform, g,
+            );
+        }
+    }
+
+    pub fn draw_accelerations(&mut self, simulator: &Simulator, softening: f64, scale: f64, c: &Context, g: &mut G2d) {
+        let points = &simulator.cluster.points;
+        let origin = simulator.origin();
+        let mut from;
+        let mut to;
+        for i in 0..self.circles.len() {
+            let acceleration = match simulator.accelerations.get(i) {
+                Some(acceleration) if simulator.accelerations.len() == points.len() => *acceleration,
+                _ => simulator.field.acceleration(&points[i], simulator.charges()[i], points, simulator.charges(), softening),
+            };
+            let magnitude = acceleration.magnitude();
+            if magnitude == 0. {
+                continue;
+            }
+            let length = ACCELERATION_LENGTH * (magnitude / ACCELERATION_FLOOR).max(1.).log10();
+            from = self.circles[i].trajectory.last();
+            to = self.transform * (points[i].state.position - origin.position + acceleration * (length / (magnitude * scale)));
+            piston_window::Line::new(YELLOW, 1.).draw_arrow(
+                [from.x, from.y, to.x, to.y],
+                ARROW_HEAD_SIZE,
+                &c.draw_state,
+                c.transform, g,
+            );
+        }
+    }
+
+    pub fn circle_at(&self, cursor: &[f64; 2]) -> Option<usize> {
+        let cursor = vec3(cursor[0], cursor[1], 0.);
+        let mut nearest: Option<(usize, f64)> = None;
+        for i in 0..self.circles.len() {
+            let distance = cursor.distance(self.circles[i].trajectory.last());
+            if distance >= self.circles[i].radius {
+                continue;
+            }
+            match nearest {
+                Some((_, min)) if min <= distance => {}
+                _ => nearest = Some((i, distance)),
+            }
+        }
+        nearest.map(|(index, _)| index)
+    }
+
+    pub fn draw_tooltip(&mut self, simulator: &Simulator, index: usize, cursor: &[f64; 2], c: &Context, g: &mut G2d, glyphs: &mut Glyphs) {
+        let body = &simulator.system.bodies[index];
+        let mass = simulator.cluster[index].mass;
+        let speed = simulator.cluster[index].state.speed.magnitude();
+        self.mass_unit.rescale(&mass);
+        self.speed_unit.units[0].rescale(&speed);
+        let lines = [
+            body.name.clone(),
+            format!("mass: {}", self.mass_unit.string_of(&mass)),
+            format!("speed: {}", self.speed_unit.string_of(&speed)),
+        ];
+        self.buffer_offset.x = cursor[0] + 12.;
+        self.buffer_offset.y = cursor[1] + 12. + HUD_FONT_SIZE as f64;
+        for line in lines.iter() {
+            piston_window::text::Text::new_color(self.circles[index].color, HUD_FONT_SIZE).draw(
+                line.as_str(),
+                glyphs,
+                &c.draw_state,
+                c.transform.trans(self.buffer_offset.x, self.buffer_offset.y),
+                g,
+            ).unwrap();
+            self.buffer_offset.y += HUD_FONT_SIZE as f64 + 4.;
+        }
+    }
+
+    pub fn draw_prediction(&mut self, positions: &Vec<Vector3>, origin: &Vector3, color: [f32; 4], c: &Context, g: &mut G2d) {
+        let mut from;
+        let mut to;
+        for k in 1..positions.len() {
+            from = self.transform * (positions[k - 1] - *origin);
+            to = self.transform * (positions[k] - *origin);
+            piston_window::line_from_to(color, 1., [from.x, from.y], [to.x, to.y], c.transform, g);
         }
     }
 
-    pub fn draw_speed(&mut self, cursor: &[f64; 2], c: &Context, g: &mut G2d) {
+    pub fn draw_ruler(&mut self, anchor: &Vector3, cursor: &[f64; 2], origin: &Vector3, c: &Context, g: &mut G2d, glyphs: &mut Glyphs) {
+        let start = self.transform * (*anchor - *origin);
+        let end = self.inverse_transform * vec3(cursor[0], cursor[1], 0.) + *origin;
+        let distance = *anchor % end;
+        piston_window::line_from_to(self.foreground, 1., [start.x, start.y], *cursor, c.transform, g);
+        self.distance_unit.rescale(&distance);
+        piston_window::text::Text::new_color(self.foreground, 16).draw(
+            format!("{}", self.distance_unit.string_of(&distance)).as_str(),
+            glyphs,
+            &c.draw_state,
+            c.transform.trans(cursor[0] + 8., cursor[1] - 8.),
+            g,
+        ).unwrap();
+    }
+
+    pub fn draw_speed(&mut self, cursor: &[f64; 2], speed: &Vector3, c: &Context, g: &mut G2d, glyphs: &mut Glyphs) {
         let last = self.circles.last().unwrap();
         let last_pos = last.trajectory.last();
         piston_window::line_from_to(
@@ -278,5 +756,54 @@ impl Drawer {
             *cursor,
             c.transform, g,
         );
+
+        let magnitude = speed.magnitude();
+        self.speed_unit.units[0].rescale(&magnitude);
+        piston_window::text::Text::new_color(last.color, 16).draw(
+            format!("{}", self.speed_unit.string_of(&magnitude)).as_str(),
+            glyphs,
+            &c.draw_state,
+            c.transform.trans(cursor[0] + 8., cursor[1] - 8.),
+            g,
+        ).unwrap();
     }
-}
\ No newline at end of file
+}
+
+pub fn grid_spacing(scale: f64) -> f64 {
+    10f64.powf((GRID_SPACING / scale).log10().ceil())
+}
+
+pub fn scaled_radius(kind: &Kind, radius: f64) -> f64 {
+    let base = match kind {
+        Kind::Star => 8.,
+        Kind::Giant => 4.,
+        Kind::Terrestrial | Kind::Hole => 2.,
+        _ => 1.,
+    };
+    base + RADIUS_LOG_FACTOR * (radius / RADIUS_REFERENCE).log10().max(0.)
+}
+
+fn heat_color(ratio: f32) -> [f32; 4] {
+    [ratio, 1. - (2. * ratio - 1.).abs(), 1. - ratio, 1.]
+}
+
+fn svg_paint(attribute: &str, color: &[f32; 4]) -> String {
+    format!(
+        "{}=\"rgb({},{},{})\" {}-opacity=\"{}\"",
+        attribute,
+        (color[0] * 255.).round() as u8,
+        (color[1] * 255.).round() as u8,
+        (color[2] * 255.).round() as u8,
+        attribute,
+        color[3],
+    )
+}
+
+fn svg_polyline(points: &Vec<Vector3>, color: &[f32; 4]) -> String {
+    let points: Vec<String> = points.iter().map(|point| format!("{},{}", point.x, point.y)).collect();
+    format!(
+        "<polyline points=\"{}\" fill=\"none\" stroke-width=\"2.5\" {}/>\n",
+        points.join(" "),
+        svg_paint("stroke", color),
+    )
+}
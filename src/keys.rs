@@ -1,47 +1,199 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
 use piston::input::{Key, MouseButton};
+use serde::{Deserialize, Serialize};
 
-pub static KEY_RESET: Key = Key::Backspace;
+pub static BUTTON_UNKNOWN: MouseButton = MouseButton::Unknown;
+pub static KEY_UNKNOWN: Key = Key::Unknown;
 
-// Config toggles
-pub static KEY_TOGGLE_TRANSLATE: Key = Key::J;
-pub static KEY_TOGGLE_TRAJECTORY: Key = Key::R;
-pub static KEY_TOGGLE_PAUSE: Key = Key::Space;
-pub static KEY_TOGGLE_ORBITS: Key = Key::Y;
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct KeyMap {
+    pub reset: Key,
+    pub restart: Key,
+    pub save_snapshot: Key,
+    pub restore_snapshot: Key,
+    pub save_system: Key,
+    pub save_config: Key,
+    pub screenshot: Key,
+    pub export_svg: Key,
+    pub toggle_record: Key,
+    pub toggle_replay_record: Key,
+    pub step: Key,
+    pub undo: Key,
+    pub duplicate: Key,
+    pub mark: Key,
+    pub circularize: Key,
+    pub remove_drift: Key,
 
-// Translation directions
-pub static KEY_DIRECTION_UP: Key = Key::W;
-pub static KEY_DIRECTION_DOWN: Key = Key::S;
-pub static KEY_DIRECTION_LEFT: Key = Key::A;
-pub static KEY_DIRECTION_RIGHT: Key = Key::D;
+    // Config toggles
+    pub toggle_translate: Key,
+    pub toggle_trajectory: Key,
+    pub toggle_current_trajectory: Key,
+    pub toggle_heatmap: Key,
+    pub toggle_pause: Key,
+    pub toggle_reverse: Key,
+    pub toggle_orbits: Key,
+    pub toggle_speed_mode: Key,
+    pub toggle_follow: Key,
+    pub toggle_speeds: Key,
+    pub toggle_accelerations: Key,
+    pub toggle_hud: Key,
+    pub toggle_grid: Key,
+    pub toggle_snap: Key,
+    pub toggle_spheres: Key,
+    pub toggle_potential: Key,
+    pub toggle_auto_rotate: Key,
+    pub toggle_auto_remove: Key,
 
-// Rotation orientations
-pub static KEY_ROTATION_UP: Key = Key::Up;
-pub static KEY_ROTATION_DOWN: Key = Key::Down;
-pub static KEY_ROTATION_LEFT: Key = Key::Left;
-pub static KEY_ROTATION_RIGHT: Key = Key::Right;
+    // Force modes
+    pub toggle_drag: Key,
+    pub toggle_spring: Key,
+    pub toggle_halo: Key,
+    pub toggle_relativistic: Key,
+    pub toggle_coulomb: Key,
+    pub increase_charge: Key,
+    pub decrease_charge: Key,
 
-// Scaling
-pub static KEY_INCREASE_OVERSAMPLING: Key = Key::P;
-pub static KEY_DECREASE_OVERSAMPLING: Key = Key::O;
+    // Translation directions
+    pub direction_up: Key,
+    pub direction_down: Key,
+    pub direction_left: Key,
+    pub direction_right: Key,
 
-pub static KEY_INCREASE_DISTANCE: Key = Key::I;
-pub static KEY_DECREASE_DISTANCE: Key = Key::U;
+    // Rotation orientations
+    pub rotation_up: Key,
+    pub rotation_down: Key,
+    pub rotation_left: Key,
+    pub rotation_right: Key,
+    pub reset_orientation: Key,
+    pub view_momentum: Key,
 
-pub static KEY_INCREASE_TIME: Key = Key::Comma;
-pub static KEY_DECREASE_TIME: Key = Key::M;
+    // Scaling
+    pub increase_oversampling: Key,
+    pub decrease_oversampling: Key,
+    pub increase_distance: Key,
+    pub decrease_distance: Key,
+    pub increase_time: Key,
+    pub decrease_time: Key,
+    pub increase_trajectory: Key,
+    pub decrease_trajectory: Key,
+    pub increase_current_index: Key,
+    pub decrease_current_index: Key,
 
-pub static KEY_INCREASE_CURRENT_INDEX: Key = Key::V;
-pub static KEY_DECREASE_CURRENT_INDEX: Key = Key::C;
+    // State transitions
+    pub next_logger_state: Key,
+    pub toggle_degrees: Key,
+    pub next_frame_state: Key,
+    pub next_method_state: Key,
+    pub next_collision_state: Key,
 
-// State transitions
-pub static KEY_NEXT_LOGGER_STATE: Key = Key::L;
-pub static KEY_NEXT_FRAME_STATE: Key = Key::K;
-pub static KEY_NEXT_METHOD_STATE: Key = Key::Semicolon;
+    pub move_add: MouseButton,
+    pub move_remove: MouseButton,
+    pub move_select: MouseButton,
+    pub wait_drop_do: MouseButton,
+    pub wait_drop_cancel: MouseButton,
+    pub measure: Key,
+    pub measure_do: MouseButton,
+    pub measure_cancel: MouseButton,
+}
 
-pub static MOUSE_MOVE_ADD: MouseButton = MouseButton::Left;
-pub static MOUSE_MOVE_REMOVE: MouseButton = MouseButton::Right;
-pub static MOUSE_WAIT_DROP_DO: MouseButton = MouseButton::Left;
-pub static MOUSE_WAIT_DROP_CANCEL: MouseButton = MouseButton::Right;
+impl KeyMap {
+    pub fn from_file(path: &Path) -> Result<KeyMap, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read key map file {}: {}", path.display(), err))?;
+        let keys = toml::from_str(&content)
+            .map_err(|err| format!("invalid key map file {}: {}", path.display(), err))?;
+        Ok(keys)
+    }
+}
 
-pub static BUTTON_UNKNOWN: MouseButton = MouseButton::Unknown;
-pub static KEY_UNKNOWN: Key = Key::Unknown;
\ No newline at end of file
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap {
+            reset: Key::Backspace,
+            restart: Key::Home,
+            save_snapshot: Key::F10,
+            restore_snapshot: Key::F11,
+            save_system: Key::F5,
+            save_config: Key::F6,
+            screenshot: Key::F12,
+            export_svg: Key::End,
+            toggle_record: Key::F9,
+            toggle_replay_record: Key::PageDown,
+            step: Key::N,
+            undo: Key::Q,
+            duplicate: Key::E,
+            mark: Key::D6,
+            circularize: Key::Tab,
+            remove_drift: Key::Period,
+
+            toggle_translate: Key::J,
+            toggle_trajectory: Key::R,
+            toggle_current_trajectory: Key::Minus,
+            toggle_heatmap: Key::D5,
+            toggle_pause: Key::Space,
+            toggle_reverse: Key::Z,
+            toggle_orbits: Key::Y,
+            toggle_speed_mode: Key::X,
+            toggle_follow: Key::F,
+            toggle_speeds: Key::G,
+            toggle_accelerations: Key::H,
+            toggle_hud: Key::T,
+            toggle_grid: Key::F1,
+            toggle_snap: Key::D9,
+            toggle_spheres: Key::F8,
+            toggle_potential: Key::D7,
+            toggle_auto_rotate: Key::F4,
+            toggle_auto_remove: Key::Delete,
+
+            toggle_drag: Key::D1,
+            toggle_spring: Key::D2,
+            toggle_halo: Key::D3,
+            toggle_relativistic: Key::D4,
+            toggle_coulomb: Key::D0,
+            increase_charge: Key::Equals,
+            decrease_charge: Key::Slash,
+
+            direction_up: Key::W,
+            direction_down: Key::S,
+            direction_left: Key::A,
+            direction_right: Key::D,
+
+            rotation_up: Key::Up,
+            rotation_down: Key::Down,
+            rotation_left: Key::Left,
+            rotation_right: Key::Right,
+            reset_orientation: Key::F2,
+            view_momentum: Key::F3,
+
+            increase_oversampling: Key::P,
+            decrease_oversampling: Key::O,
+            increase_distance: Key::I,
+            decrease_distance: Key::U,
+            increase_time: Key::Comma,
+            decrease_time: Key::M,
+            increase_trajectory: Key::RightBracket,
+            decrease_trajectory: Key::LeftBracket,
+            increase_current_index: Key::V,
+            decrease_current_index: Key::C,
+
+            next_logger_state: Key::L,
+            toggle_degrees: Key::F7,
+            next_frame_state: Key::K,
+            next_method_state: Key::Semicolon,
+            next_collision_state: Key::B,
+
+            move_add: MouseButton::Left,
+            move_remove: MouseButton::Right,
+            move_select: MouseButton::Middle,
+            wait_drop_do: MouseButton::Left,
+            wait_drop_cancel: MouseButton::Right,
+            measure: Key::D8,
+            measure_do: MouseButton::Left,
+            measure_cancel: MouseButton::Right,
+        }
+    }
+}
@@ -1,25 +1,37 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use dynamics::orbital;
-use dynamics::orbital::Body;
+use dynamics::orbital::{Body, Orbit};
 use dynamics::point::Point3;
-use dynamics::solver::{Method, Solver};
+use dynamics::solver::Solver;
 use geomath::point;
 use geomath::prelude::*;
-use geomath::trajectory::Trajectory3;
-use geomath::vector::vec3;
+use geomath::vector;
+use geomath::vector::{vec2, vec3, Vector3};
 use piston::input::{Event, Key, MouseButton, UpdateArgs};
 use piston_window;
 use piston_window::{Glyphs, PistonWindow};
 
 use crate::common::*;
-use crate::core::{Config, Simulator, Status};
-use crate::draw::{Circle, Drawer};
+use crate::core::{Collision, Config, Edit, Simulator, Snapshot, Status};
+use crate::draw::{Circle, Drawer, grid_spacing, scaled_radius, Trail};
+use crate::keys::KeyMap;
 use crate::log::Logger;
+use crate::orbit::Kepler;
+use crate::physics::Octree;
+use crate::record::{Format, Recorder, Replay};
 
 pub mod common;
 pub mod core;
 pub mod draw;
 pub mod log;
 pub mod keys;
+pub mod orbit;
+pub mod physics;
+pub mod record;
 
 pub struct App {
     pub simulator: Simulator,
@@ -27,6 +39,11 @@ pub struct App {
     pub status: Status,
     pub logger: Logger,
     pub drawer: Drawer,
+    pub recorder: Recorder,
+    pub keys: KeyMap,
+    pub snapshot: Option<Snapshot>,
+    pub replay: Option<Replay>,
+    pub ruler: Option<Vector3>,
 }
 
 impl App {
@@ -34,42 +51,156 @@ impl App {
         let size = config.size.clone();
         let scale = config.scale.distance;
         let drawer = Drawer::new(&simulator, &config.orientation, scale, &size);
+        let logger = match &config.log {
+            None => Logger::new(),
+            Some(path) => Logger::new_to_file(Path::new(path)).unwrap_or_else(|err| {
+                eprintln!("Error during log file creation: {}", err);
+                Logger::new()
+            }),
+        };
+        let keys = match &config.keys {
+            None => KeyMap::default(),
+            Some(path) => KeyMap::from_file(Path::new(path)).unwrap_or_else(|err| {
+                eprintln!("Error during key map loading: {}", err);
+                KeyMap::default()
+            }),
+        };
         let mut ret = App {
             simulator,
             config,
             status: Status::new(),
-            logger: Logger::new(),
+            logger,
             drawer,
+            recorder: Recorder::new(),
+            keys,
+            snapshot: None,
+            replay: None,
+            ruler: None,
         };
+        ret.simulator.solver.method = ret.config.method;
+        ret.simulator.set_field(ret.config.field());
         ret.drawer.set_appearance(&ret.simulator.system);
+        if let Some(palette) = &ret.config.palette {
+            ret.drawer.foreground = palette.foreground;
+        }
+        ret.drawer.resize_circles(ret.config.trajectory_length);
+        ret.drawer.reset_circles(&ret.simulator);
         ret
     }
 
     pub fn from_orbital(system: orbital::Cluster, config: Config) -> App {
-        let solver = Solver::new(1., 1, Method::RungeKutta4);
+        let solver = Solver::new(1., 1, config.method);
         let simulator = Simulator::orbital_at(system, 0., solver);
         App::new(simulator, config)
     }
 
+    pub fn from_replay(replay: Replay, config: Config) -> App {
+        let mut simulator = Simulator::from(dynamics::Cluster::empty());
+        simulator.restore(replay.first());
+        let mut ret = App::new(simulator, config);
+        ret.replay = Some(replay);
+        ret
+    }
+
+    pub fn from_random(count: usize, config: Config) -> App {
+        let radius = 0.5 * config.size.width.min(config.size.height) / config.scale.distance;
+        let solver = Solver::new(1., 1, config.method);
+        let simulator = Simulator::random(count, radius, config.gravity, solver, &mut seeded_rng(config.seed));
+        App::new(simulator, config)
+    }
+
     pub fn on_key(&mut self, key: &Key) {
-        self.config.update(key);
-        self.logger.update(key);
-        self.simulator.update(&Some(*key), self.status.is_waiting_to_add());
-        self.status.update(&Some(*key), &Option::None);
+        if *key == self.keys.save_system {
+            self.save_system();
+        } else if *key == self.keys.save_config {
+            self.save_config();
+        } else if *key == self.keys.export_svg {
+            self.save_figure();
+        } else if *key == self.keys.toggle_record {
+            self.toggle_record(Format::Csv);
+        } else if *key == self.keys.toggle_replay_record {
+            self.toggle_record(Format::Frames);
+        } else if *key == self.keys.undo && !self.status.is_waiting_to_add() {
+            self.undo();
+        } else if *key == self.keys.restart && !self.status.is_waiting_to_add() {
+            self.restart();
+        } else if *key == self.keys.save_snapshot && !self.status.is_waiting_to_add() {
+            self.snapshot = Some(self.simulator.snapshot());
+        } else if *key == self.keys.restore_snapshot && !self.status.is_waiting_to_add() {
+            self.restore_snapshot();
+        } else if *key == self.keys.duplicate && !self.status.is_waiting_to_add() {
+            self.duplicate();
+        } else if *key == self.keys.circularize && !self.status.is_waiting_to_add() {
+            let current = self.simulator.current_index();
+            self.simulator.place_in_circular_orbit(current, None);
+        } else if *key == self.keys.toggle_current_trajectory {
+            if let Some(circle) = self.drawer.circles.get_mut(self.simulator.current_index()) {
+                circle.show_trajectory = !circle.show_trajectory;
+            }
+        } else if *key == self.keys.increase_charge || *key == self.keys.decrease_charge {
+            let current = self.simulator.current_index();
+            let step = if *key == self.keys.increase_charge { self.config.charge_step } else { -self.config.charge_step };
+            if let Some(charge) = self.simulator.charges().get(current).copied() {
+                self.simulator.set_charge(current, charge + step);
+            }
+        } else if *key == self.keys.mark {
+            self.simulator.mark_current();
+        } else if *key == self.keys.remove_drift {
+            self.simulator.remove_barycenter_drift();
+        } else if *key == self.keys.view_momentum {
+            self.view_momentum();
+        }
+        self.config.update(key, &self.keys);
+        self.simulator.set_field(self.config.field());
+        self.drawer.resize_circles(self.config.trajectory_length);
+        self.logger.update(key, &self.keys);
+        self.simulator.update(&Some(*key), self.status.is_waiting_to_add(), &self.keys);
+        self.status.update(&Some(*key), &Option::None, &self.keys);
     }
 
     pub fn on_click(&mut self, button: &MouseButton) {
-        self.status.update(&Option::None, &Some(*button));
+        self.status.update(&Option::None, &Some(*button), &self.keys);
+    }
+
+    pub fn on_scroll(&mut self, scroll: &[f64; 2], cursor: &[f64; 2]) {
+        let factor = self.config.scale.zoom_distance(ZOOM_SCALING_FACTOR.powf(scroll[1]));
+        self.drawer.zoom_at(cursor, factor, &self.config.size);
+        self.status.update_transform = true;
+        self.status.reset_circles = true;
     }
 
     pub fn render(&mut self, cursor: &[f64; 2], window: &mut PistonWindow, event: &Event, glyphs: &mut Glyphs) {
         let scale = self.config.scale.distance;
+        let background = self.config.background();
+        let prediction = if self.status.state == core::State::WaitSpeed {
+            let index = self.simulator.last_index();
+            let period = self.simulator.system[index].orbit.period();
+            let duration = if period.is_finite() && period > 0. { period } else { self.config.scale.time * PREDICTION_HORIZON };
+            self.simulator.predict(index, duration, self.config.softening)
+        } else {
+            vec![]
+        };
         self.logger.print(true);
         self.logger.clear();
         window.draw_2d(
             event,
             |c, g, device| {
-                piston_window::clear(BLACK, g);
+                piston_window::clear(background, g);
+                if self.config.potential {
+                    self.drawer.draw_potential_field(
+                        &self.simulator,
+                        self.config.potential_resolution,
+                        self.config.softening,
+                        &self.config.size,
+                        &c, g,
+                    );
+                }
+                if self.config.grid {
+                    self.drawer.draw_grid(scale, &self.config.size, &c, g);
+                }
+                if self.config.hud {
+                    self.drawer.draw_hud(&self.simulator, &self.config, &c, g, glyphs);
+                }
                 if self.simulator.cluster.is_empty() {
                     self.drawer.draw_barycenter(&self.simulator, &c, g);
                     self.drawer.draw_scale(scale, &self.config.size, &c, g, glyphs);
@@ -78,17 +209,40 @@ impl App {
                     return;
                 }
                 if self.config.trajectory {
-                    self.drawer.draw_trajectories(&c, g);
+                    self.drawer.draw_trajectories(self.config.heatmap, self.config.trajectory_stride, &c, g);
                 }
 
                 if self.config.orbits {
                     self.drawer.draw_orbits(&self.simulator, &c, g);
                 }
 
+                if self.config.spheres {
+                    self.drawer.draw_spheres_of_influence(&self.simulator, scale, &c, g);
+                }
+
+                if self.config.speeds {
+                    self.drawer.draw_speeds(&self.simulator, self.config.velocity_scale, scale, &c, g);
+                }
+
+                if self.config.accelerations {
+                    self.drawer.draw_accelerations(&self.simulator, self.config.softening, scale, &c, g);
+                }
+
                 if self.status.state == core::State::WaitSpeed {
-                    self.drawer.draw_speed(cursor, &c, g);
+                    let origin = self.simulator.origin().position;
+                    let color = self.drawer.circles.last().unwrap().color;
+                    self.drawer.draw_prediction(&prediction, &origin, color, &c, g);
+                    let speed = self.simulator.last().unwrap().state.speed;
+                    self.drawer.draw_speed(cursor, &speed, &c, g, glyphs);
                 }
                 self.drawer.draw_points(&c, g);
+                if let (core::State::Measure, Some(anchor)) = (self.status.state, &self.ruler) {
+                    let origin = self.simulator.origin().position;
+                    self.drawer.draw_ruler(anchor, cursor, &origin, &c, g, glyphs);
+                }
+                if let Some(index) = self.drawer.circle_at(cursor) {
+                    self.drawer.draw_tooltip(&self.simulator, index, cursor, &c, g, glyphs);
+                }
                 self.drawer.draw_barycenter(&self.simulator, &c, g);
                 self.drawer.draw_scale(scale, &self.config.size, &c, g, glyphs);
                 self.drawer.draw_basis(&self.config.size, &c, g);
@@ -100,21 +254,42 @@ impl App {
     pub fn update(&mut self, _window: &mut PistonWindow, args: &UpdateArgs, cursor: &[f64; 2]) {
         use crate::core::State::*;
 
-        if let Some(index) = self.simulator.remove_aways() {
-            self.drawer.circles.remove(index);
+        let replaying = self.replay.is_some();
+        let current = self.simulator.current_index();
+        if self.config.auto_remove && !replaying {
+            if let Some(index) = self.simulator.remove_aways(self.config.away_sigma) {
+                self.remove_circle(index, current);
+            }
+        }
+
+        if !self.status.is_waiting_to_add() && !replaying {
+            self.do_collide();
         }
 
         match self.status.state {
-            Move => self.do_move(args.dt),
+            Move => if replaying { self.do_replay() } else { self.do_move(args.dt) },
             Translate => self.do_translate(),
             Reset => self.do_reset(),
             Add => self.do_add(),
             Remove => self.do_remove(cursor),
+            Select => self.do_select(cursor),
             WaitDrop => self.do_wait_drop(cursor),
             WaitSpeed => self.do_wait_speed(cursor),
-            CancelDrop => self.do_cancel_drop()
+            CancelDrop => self.do_cancel_drop(),
+            Anchor => self.ruler = None,
+            Measure => self.do_measure(cursor),
         };
 
+        if self.config.follow {
+            self.do_follow();
+        }
+
+        if self.config.auto_rotate && self.status.frame % AUTO_ROTATE_PERIOD == 0 {
+            self.config.orientation.increment_z();
+            self.status.update_transform = true;
+            self.status.reset_circles = true;
+        }
+
         if self.status.update_transform {
             self.drawer.update_transform(&self.config.orientation, self.config.scale.distance, &self.config.size);
         }
@@ -125,7 +300,197 @@ impl App {
 
         self.drawer.update_circles(&self.simulator);
 
-        self.status.clear();
+        self.status.clear(&self.keys);
+    }
+
+    pub fn step(&mut self, dt: f64) -> usize {
+        if self.simulator.cluster.is_empty() {
+            return 0;
+        }
+        let time_scale = if self.config.reverse { -self.config.scale.time } else { self.config.scale.time };
+        self.status.step.push(dt, time_scale);
+        let dt = dt / self.config.oversampling as f64 * time_scale;
+        let softening = self.config.softening;
+        let theta = self.config.barnes_hut_theta;
+        let field = self.simulator.field;
+        let gravity = field.gravity;
+        let mut positions: Vec<Vector3> = vec![];
+        let mut batch: Vec<Vector3> = vec![];
+        let current = self.simulator.current_index();
+        let mut accelerations = std::mem::replace(&mut self.simulator.accelerations, vec![]);
+        let diverged = self.simulator.apply(dt, self.config.oversampling, |points, charges, i| {
+            let mut acceleration = if let Some(stiffness) = field.spring {
+                physics::spring(&points[i].state.position, stiffness)
+            } else {
+                // Cluster::apply evaluates all the bodies of a stage before the next one, so the batch
+                // is rebuilt at the first body. A body away from the snapshot means stages interleave.
+                let stale = i == 0 || positions.len() != points.len() || positions[i] % points[i].state.position != 0.;
+                if stale {
+                    positions = points.iter().map(|point| point.state.position).collect();
+                    batch = match theta {
+                        Some(theta) => Octree::new(points).acceleration_all(points, theta, gravity, softening),
+                        None => physics::gravity_all(points, gravity, softening),
+                    };
+                }
+                batch[i]
+            };
+            acceleration += field.perturbation(&points[i], charges[i], points, charges);
+            if accelerations.len() != points.len() {
+                accelerations.resize(points.len(), vector::consts::ZEROS_3);
+            }
+            accelerations[i] = acceleration;
+            physics::derivative(&points[i].state.speed, &acceleration)
+        });
+        accelerations.truncate(self.simulator.cluster.len());
+        self.simulator.accelerations = accelerations;
+        self.status.step.dt = self.simulator.solver.dt;
+        for (index, body) in diverged.iter() {
+            self.logger.warn(&format!("{} diverged to a non-finite state and was removed", body.name));
+            self.remove_circle(*index, current);
+        }
+        if let Err(err) = self.recorder.record(self.status.step.elapsed, &self.simulator) {
+            eprintln!("Error during trajectory recording: {}", err);
+            self.recorder.disable().ok();
+        }
+        diverged.len()
+    }
+
+    pub fn run_headless(&mut self, steps: u64, dt: f64) -> (f64, usize) {
+        let mut diverged = 0;
+        for _ in 0..steps {
+            diverged += self.step(dt);
+        }
+        self.logger.clear();
+        self.logger.log_summary(&self.simulator);
+        self.logger.print(false);
+        (self.simulator.field.total_energy(&self.simulator.cluster, self.simulator.charges()), diverged)
+    }
+
+    pub fn save_system(&self) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let path = format!("system-{}.json", timestamp);
+        if let Err(err) = self.simulator.save(Path::new(&path)) {
+            eprintln!("Error during cluster saving: {}", err);
+        }
+    }
+
+    pub fn save_config(&self) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let path = format!("config-{}.toml", timestamp);
+        if let Err(err) = self.config.save(Path::new(&path)) {
+            eprintln!("Error during config saving: {}", err);
+        }
+    }
+
+    pub fn save_figure(&self) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let path = format!("screenshots/figure-{}.svg", timestamp);
+        if let Err(err) = self.export_svg(Path::new(&path)) {
+            eprintln!("Error during SVG export: {}", err);
+        }
+    }
+
+    pub fn export_svg(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.drawer.svg(&self.simulator, &self.config))?;
+        Ok(())
+    }
+
+    pub fn toggle_record(&mut self, format: Format) {
+        let result = if self.recorder.is_enabled() {
+            self.recorder.disable()
+        } else {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            let path = match format {
+                Format::Csv => format!("records/trajectory-{}.csv", timestamp),
+                Format::Frames => format!("records/replay-{}.jsonl", timestamp),
+            };
+            self.recorder.enable(Path::new(&path), format, &self.simulator)
+        };
+        if let Err(err) = result {
+            eprintln!("Error during trajectory recording: {}", err);
+        }
+    }
+
+    pub fn view_momentum(&mut self) {
+        let momentum = physics::angular_momentum_vector(&self.simulator.cluster);
+        if let Some(orientation) = Orientation::from_normal(&momentum) {
+            self.config.orientation = orientation;
+        }
+    }
+
+    pub fn duplicate(&mut self) {
+        let separation = match self.drawer.circles.get(self.simulator.current_index()) {
+            Some(circle) => 2. * circle.radius / self.config.scale.distance,
+            None => 0.,
+        };
+        if let Some(index) = self.simulator.duplicate_current(separation) {
+            let body = &self.simulator.system.bodies[index];
+            self.drawer.circles.push(
+                Circle::new(Trail::zeros(self.config.trajectory_length), scaled_radius(&body.kind, body.radius), body.color)
+            );
+            self.simulator.set_current(index);
+        }
+    }
+
+    pub fn undo(&mut self) {
+        match self.simulator.undo() {
+            Some(Edit::Add(index)) => {
+                self.drawer.circles.remove(index);
+            }
+            Some(Edit::Remove(index, _, body, _)) => {
+                let circle = Circle::new(
+                    Trail::zeros(self.config.trajectory_length),
+                    scaled_radius(&body.kind, body.radius),
+                    body.color,
+                );
+                self.drawer.circles.insert(index, circle);
+            }
+            None => {}
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.simulator.restart();
+        self.rebuild_circles();
+        self.status.step = Step::new();
+    }
+
+    pub fn restore_snapshot(&mut self) {
+        if let Some(snapshot) = &self.snapshot {
+            self.simulator.restore(snapshot);
+            self.rebuild_circles();
+        }
+    }
+
+    fn rebuild_circles(&mut self) {
+        let length = self.config.trajectory_length;
+        self.drawer.circles = self.simulator.system.bodies.iter()
+            .map(|body| Circle::new(Trail::zeros(length), scaled_radius(&body.kind, body.radius), body.color))
+            .collect();
+        self.status.reset_circles = true;
+    }
+
+    pub fn save_screenshot(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let width = self.config.size.width as u32;
+        let height = self.config.size.height as u32;
+        let mut pixels = vec![0u8; (4 * width * height) as usize];
+        unsafe {
+            gl::ReadPixels(
+                0, 0,
+                width as i32, height as i32,
+                gl::RGBA, gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+        let flipped: Vec<u8> = pixels.chunks(4 * width as usize).rev().flatten().cloned().collect();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        image::save_buffer(path, &flipped, width, height, image::ColorType::Rgba8)?;
+        Ok(())
     }
 
     //noinspection RsTypeCheck
@@ -139,6 +504,33 @@ impl App {
         );
     }
 
+    fn remove_circle(&mut self, index: usize, current: usize) {
+        self.drawer.circles.remove(index);
+        if index == current {
+            self.config.follow = false;
+        }
+    }
+
+    fn do_collide(&mut self) {
+        let scale = self.config.scale.distance;
+        let radii = self.drawer.circles.iter().map(|circle| circle.radius / scale).collect();
+        let current = self.simulator.current_index();
+        match self.config.collision {
+            Collision::Merge => {
+                let merged = self.simulator.merge_collisions(&radii);
+                for index in merged.iter() {
+                    self.remove_circle(*index, current);
+                }
+                if !merged.is_empty() {
+                    self.drawer.set_appearance(&self.simulator.system);
+                }
+            }
+            Collision::Bounce => {
+                self.simulator.resolve_elastic(&radii, self.config.restitution);
+            }
+        };
+    }
+
     fn do_translate(&mut self) {
         if self.simulator.cluster.is_empty() {
             return;
@@ -148,29 +540,65 @@ impl App {
         self.simulator.cluster.translate_at(self.simulator.current, &direction);
     }
 
+    fn do_follow(&mut self) {
+        let position = match self.simulator.current() {
+            None => {
+                self.config.follow = false;
+                return;
+            }
+            Some(point) => point.state.position,
+        };
+        let target = self.drawer.transform * (position - self.simulator.origin().position);
+        let center = vec2(self.config.size.width * 0.5, self.config.size.height * 0.5);
+        let pan = self.drawer.pan + center - vec2(target.x, target.y);
+        self.drawer.pan = lerp_clamped(self.drawer.pan, pan, FOLLOW_SMOOTHING_FACTOR);
+        self.status.update_transform = true;
+        self.status.reset_circles = true;
+    }
+
     fn do_move(&mut self, dt: f64) {
-        use dynamics::forces;
-        if self.config.pause || self.simulator.cluster.is_empty() {
+        if self.config.pause && !self.status.single_step {
+            self.status.step.hold();
             return;
         }
-        self.status.step.push(dt, self.config.scale.time);
-        let dt = dt / self.config.oversampling as f64 * self.config.scale.time;
-        self.simulator.apply(dt, self.config.oversampling, |points, i| {
-            forces::gravity(&points[i], points)
-        });
+        self.step(dt);
+    }
+
+    fn do_replay(&mut self) {
+        if self.config.pause && !self.status.single_step {
+            self.status.step.hold();
+            return;
+        }
+        let snapshot = match self.replay.as_mut().and_then(|replay| replay.next()) {
+            None => return,
+            Some(snapshot) => snapshot.clone(),
+        };
+        let frame = self.simulator.frame;
+        let current = self.simulator.current_index();
+        let count = self.simulator.cluster.len();
+        self.simulator.restore(&snapshot);
+        self.simulator.frame = frame;
+        self.simulator.set_current(current);
+        if self.simulator.cluster.len() != count {
+            self.rebuild_circles();
+        }
     }
 
     fn do_reset(&mut self) {
         if !self.simulator.cluster.is_empty() {
-            self.simulator.cluster.reset0_at(self.simulator.current);
+            let current = self.simulator.current_index();
+            self.simulator.reset0_at(current);
         }
     }
 
     //noinspection RsTypeCheck
     fn do_add(&mut self) {
-        let body = Body::random();
+        let mut body = Body::random();
+        if let Some(palette) = &self.config.palette {
+            body.color = palette.color(self.simulator.cluster.len());
+        }
         self.drawer.circles.push(
-            Circle::new(Trajectory3::zeros(), body.kind.scaled_radius(body.radius), body.color)
+            Circle::new(Trail::zeros(self.config.trajectory_length), scaled_radius(&body.kind, body.radius), body.color)
         );
         self.simulator.push(Point3::new(point::Point3::zeros(), body.mass), body);
     }
@@ -180,19 +608,36 @@ impl App {
         let cursor = vec3(cursor[0], cursor[1], 0.);
         for i in 0..self.simulator.cluster.len() {
             if cursor.distance(self.drawer.circles[i].trajectory.last()) < self.drawer.circles[i].radius {
-                self.simulator.cluster.remove(i);
-                self.drawer.circles.remove(i);
+                let current = self.simulator.current_index();
+                self.simulator.remove(i);
+                self.remove_circle(i, current);
                 break;
             }
         }
     }
 
+    fn do_select(&mut self, cursor: &[f64; 2]) {
+        if let Some(index) = self.drawer.circle_at(cursor) {
+            self.simulator.set_current(index);
+        }
+    }
+
     fn do_wait_drop(&mut self, cursor: &[f64; 2]) {
-        let cursor = vec3(cursor[0], cursor[1], 0.);
-        let transformed_cursor = self.drawer.inverse_transform * cursor;
+        let mut cursor = vec3(cursor[0], cursor[1], 0.);
+        let mut transformed_cursor = self.drawer.inverse_transform * cursor;
+        if self.config.snap {
+            let spacing = self.config.snap_spacing.unwrap_or_else(|| grid_spacing(self.config.scale.distance));
+            transformed_cursor = vec3(
+                (transformed_cursor.x / spacing).round() * spacing,
+                (transformed_cursor.y / spacing).round() * spacing,
+                (transformed_cursor.z / spacing).round() * spacing,
+            );
+            cursor = self.drawer.transform * transformed_cursor;
+        }
         let last_index = self.simulator.cluster.len() - 1;
         self.drawer.circles[last_index].trajectory.reset(&cursor);
-        self.simulator.cluster.reset_position_at(last_index, &transformed_cursor);
+        self.drawer.circles[last_index].history.reset(&transformed_cursor);
+        self.simulator.reset_position_at(last_index, &transformed_cursor);
     }
 
     //noinspection RsTypeCheck
@@ -200,12 +645,26 @@ impl App {
         let cursor = self.drawer.inverse_transform * vec3(cursor[0], cursor[1], 0.);
         let last_index = self.simulator.cluster.len() - 1;
         let point = &self.simulator.cluster[last_index];
-        let speed = (cursor - point.state.position) * SPEED_SCALING_FACTOR;
+        let speed = self.config.speed_mode.speed(&(cursor - point.state.position), &self.config.scale);
         self.simulator.cluster.reset_speed_at(last_index, &speed);
+        let point = &self.simulator.cluster[last_index];
+        let barycenter = self.simulator.cluster.barycenter();
+        self.simulator.system.bodies[last_index].orbit = Orbit::from_state(
+            &(point.state.position - barycenter.state.position),
+            &(point.state.speed - barycenter.state.speed),
+            self.simulator.field.gravity * barycenter.mass,
+        );
+    }
+
+    fn do_measure(&mut self, cursor: &[f64; 2]) {
+        if self.ruler.is_none() {
+            let cursor = vec3(cursor[0], cursor[1], 0.);
+            self.ruler = Some(self.drawer.inverse_transform * cursor + self.simulator.origin().position);
+        }
     }
 
     fn do_cancel_drop(&mut self) {
-        self.simulator.cluster.pop();
+        self.simulator.pop();
         self.drawer.circles.pop();
     }
 }
\ No newline at end of file
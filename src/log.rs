@@ -1,3 +1,8 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
 use dynamics::point::Point3;
 use geomath::point;
 use geomath::prelude::Metric;
@@ -9,7 +14,10 @@ use crate::common::*;
 use crate::common::Scale;
 use crate::core;
 use crate::draw::{Circle, Drawer};
-use crate::keys::KEY_NEXT_LOGGER_STATE;
+use crate::keys::KeyMap;
+use crate::orbit::Kepler;
+use crate::physics;
+use crate::physics::Field;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum State {
@@ -20,6 +28,7 @@ pub enum State {
     Cinematic,
     Points,
     Physics,
+    History,
     Bodies,
 }
 
@@ -34,7 +43,8 @@ impl State {
             Cinematic => Points,
             Points => Bodies,
             Bodies => Physics,
-            Physics => Hide,
+            Physics => History,
+            History => Hide,
         };
     }
 }
@@ -47,6 +57,7 @@ pub struct Logger {
     energy_unit: Unit,
     time_unit: Unit,
     distance_unit: Unit,
+    sink: Option<File>,
 }
 
 impl Logger {
@@ -60,12 +71,21 @@ impl Logger {
             energy_unit: Unit::from(unitflow::Scale::from(Energy::Joules)),
             time_unit: Unit::from(unitflow::Scale::from(Time::Second)),
             distance_unit: Unit::from(unitflow::Scale::from(Distance::Meter)),
+            sink: None,
         }
     }
 
-    pub fn update(&mut self, key: &Key) {
-        if *key == KEY_NEXT_LOGGER_STATE {
+    pub fn new_to_file(path: &Path) -> io::Result<Logger> {
+        let mut ret = Logger::new();
+        ret.sink = Some(File::create(path)?);
+        Ok(ret)
+    }
+
+    pub fn update(&mut self, key: &Key, keys: &KeyMap) {
+        if *key == keys.next_logger_state {
             self.state.next();
+        } else if *key == keys.toggle_degrees {
+            self.units.toggle_angle();
         }
     }
 
@@ -73,7 +93,26 @@ impl Logger {
         self.buffer.clear();
     }
 
+    pub fn warn(&self, message: &str) {
+        if let Some(mut sink) = self.sink.as_ref() {
+            if let Err(err) = writeln!(sink, "warning: {}", message) {
+                eprintln!("Error during log writing: {}", err);
+            }
+            return;
+        }
+        eprintln!("warning: {}", message);
+    }
+
     pub fn print(&self, clear_screen: bool) {
+        if let Some(mut sink) = self.sink.as_ref() {
+            if self.buffer.trim().is_empty() {
+                return;
+            }
+            if let Err(err) = writeln!(sink, "{}", self.buffer) {
+                eprintln!("Error during log writing: {}", err);
+            }
+            return;
+        }
         if clear_screen {
             print!("{}[2J", 27 as char);
         }
@@ -93,19 +132,58 @@ impl Logger {
             Hide => (),
             Status => self.log_status(status, input),
             Config => self.log_config(config),
-            Step => self.log_step(&status.step),
+            Step => self.log_step(&status.step, config.oversampling),
             Cinematic => self.log_cinematic(simulator.current_index(), drawer, status),
             Points => self.log_points(simulator, status),
             Bodies => self.log_cluster(&simulator.cluster),
-            Physics => self.log_physics(simulator)
+            Physics => self.log_physics(simulator),
+            History => self.log_history(simulator),
+        };
+        match self.state {
+            Step | Physics => self.log_accuracy(simulator, config.min_steps_per_orbit),
+            _ => ()
         };
         self.buffer += "\n";
         match self.state {
-            Step | Points | Cinematic | Physics => self.log_scale(&config.scale),
+            Step | Points | Cinematic | Physics | History => self.log_scale(&config.scale),
             _ => ()
         };
     }
 
+    fn log_approach(&mut self, simulator: &core::Simulator, approach: &core::Approach) {
+        self.buffer += &format!("\n*** closest approach ***\nbodies: {} / {}",
+                                simulator.system[approach.first].name,
+                                simulator.system[approach.second].name);
+        self.distance_unit.rescale(&approach.distance);
+        self.buffer += &format!("\ncurrent: {}", self.distance_unit.string_of(&approach.distance));
+        self.distance_unit.rescale(&approach.minimum);
+        self.time_unit.rescale(&approach.time);
+        self.buffer += &format!("\nminimum: {} at {}",
+                                self.distance_unit.string_of(&approach.minimum),
+                                self.time_unit.string_of(&approach.time));
+    }
+
+    fn log_accuracy(&mut self, simulator: &core::Simulator, min_steps: f64) {
+        let period = match simulator.shortest_period() {
+            None => return,
+            Some(period) => period,
+        };
+        let dt = simulator.solver.dt.abs();
+        if dt == 0. || period / dt >= min_steps {
+            return;
+        }
+        self.time_unit.rescale(&period);
+        self.buffer += &format!("\nwarning: {:.1} steps per orbit of period {}, decrease the time scale or increase the oversampling",
+                                period / dt,
+                                self.time_unit.string_of(&period));
+    }
+
+    pub fn log_summary(&mut self, simulator: &core::Simulator) {
+        self.log_cluster(&simulator.cluster);
+        self.buffer += "\n";
+        self.log_energy(&simulator.cluster, simulator.charges(), &simulator.field);
+    }
+
     fn log_status(&mut self, status: &core::Status, input: &Input) {
         self.buffer += &format!("\
 *** status info ***
@@ -120,7 +198,7 @@ pressed keyboard key: '{:?}'",
         self.buffer += &format!("*** config info ***\n{:#?}", config)[..];
     }
 
-    fn log_step(&mut self, step: &Step) {
+    fn log_step(&mut self, step: &Step, oversampling: u32) {
         use unitflow::*;
         let frame = step.frame.value();
         let system = step.system.value();
@@ -131,13 +209,23 @@ pressed keyboard key: '{:?}'",
 dt: {} fps: {} (update)
 dt: {} fps: {} (system)
 total: {:?}
-simulated: {:?}",
+simulated: {}{:?}",
                                 self.time_unit.string_of(&frame),
                                 framerate,
                                 self.time_unit.string_of(&system),
                                 framerate_system,
                                 step.total,
+                                if step.elapsed < 0. { "-" } else { "" },
                                 step.simulated);
+        self.time_unit.rescale(&step.dt);
+        self.buffer += &format!("\ndt: {} (integration)", self.time_unit.string_of(&step.dt));
+        self.buffer += &match step.real_time_factor() {
+            None => String::from("\nreal time factor: n/a"),
+            Some(factor) => format!("\nreal time factor: {:.3e}", factor),
+        };
+        let iteration = step.system.value() / oversampling as f64;
+        self.time_unit.rescale(&iteration);
+        self.buffer += &format!("\noversampling: {} ({} per iteration)", oversampling, self.time_unit.string_of(&iteration));
     }
 
     fn log_cinematic(&mut self, current: usize, drawer: &Drawer, status: &core::Status) {
@@ -163,6 +251,15 @@ simulated: {:?}",
         let point = simulator.current().unwrap();
         let body = &simulator.system[simulator.current_index()];
         self.log_point(point, body.name.as_str());
+        let kinetic_energy = 0.5 * point.mass * point.state.speed.magnitude().powi(2);
+        let potential_energy = simulator.field.point_potential_energy(&simulator.cluster.points, simulator.charges(), simulator.current_index());
+        self.energy_unit.rescale(&(kinetic_energy + potential_energy));
+        self.buffer += &format!("\nkinetic energy: {}\npotential energy: {}",
+                                self.energy_unit.string_of(&kinetic_energy),
+                                self.energy_unit.string_of(&potential_energy));
+        if simulator.field.coulomb {
+            self.buffer += &format!("\ncharge: {:.3e} C", simulator.charges()[simulator.current_index()]);
+        }
         if status.is_waiting_to_add() && len != 1 {
             self.buffer += "\n";
             self.log_point(simulator.last().unwrap(), &simulator.system[simulator.last_index()].name);
@@ -179,8 +276,56 @@ simulated: {:?}",
         }
     }
     fn log_physics(&mut self, simulator: &core::Simulator) {
-        self.log_energy(&simulator.cluster);
-        self.buffer += &format!("\n*** orbital ***\n{:#?}", simulator.system[simulator.current_index()].orbit);
+        self.log_energy(&simulator.cluster, simulator.charges(), &simulator.field);
+        self.buffer += &format!("\n*** energy drift ***\ncurrent: {:.10e}\nmax: {:.10e}",
+                                simulator.energy_history.drift(),
+                                simulator.energy_history.max_drift);
+        let barycenter_drift = simulator.barycenter_drift();
+        self.distance_unit.rescale(&barycenter_drift);
+        self.buffer += &format!("\nbarycenter drift: {}", self.distance_unit.string_of(&barycenter_drift));
+        if let Some(approach) = &simulator.approach {
+            self.log_approach(simulator, approach);
+        }
+        let (primary, orbit) = match simulator.relative_orbit(simulator.current_index()) {
+            Some((primary, orbit)) => (simulator.system[primary].name.as_str(), orbit),
+            None => ("origin", simulator.system[simulator.current_index()].orbit.clone()),
+        };
+        let period = orbit.period();
+        self.time_unit.rescale(&period);
+        self.distance_unit.rescale(&orbit.apoapsis);
+        self.buffer += &format!("\n*** orbital ***\nprimary: {}\nmu: {:.6e}\napoapsis: {}\nperiapsis: {}\nperiod: {}",
+                                primary,
+                                orbit.mu,
+                                self.distance_unit.string_of(&orbit.apoapsis),
+                                self.distance_unit.string_of(&orbit.periapsis),
+                                self.time_unit.string_of(&period));
+        self.buffer += &format!("\nargument: {}\ninclination: {} (node {})",
+                                self.units.angle.string_of(&orbit.argument),
+                                self.units.angle.string_of(&orbit.inclination.value),
+                                self.units.angle.string_of(&orbit.inclination.argument));
+    }
+
+    fn log_history(&mut self, simulator: &core::Simulator) {
+        let energy = &simulator.energy_history;
+        let (min, max) = (energy.min(), energy.max());
+        self.energy_unit.rescale(&energy.last);
+        self.buffer += &format!("\
+*** energy history ***
+min: {}
+max: {}
+drift: {:.10e}",
+                                self.energy_unit.string_of(&min),
+                                self.energy_unit.string_of(&max),
+                                energy.drift());
+        let momentum = &simulator.momentum_history;
+        self.buffer += &format!("\n\
+*** angular momentum history ***
+min: {:.10e}
+max: {:.10e}
+drift: {:.10e}",
+                                momentum.min(),
+                                momentum.max(),
+                                momentum.drift());
     }
 
     fn log_shape(&mut self, circle: &Circle) {
@@ -195,13 +340,12 @@ simulated: {:?}",
         self.buffer += &format!("{}", self.units.string_of(point));
     }
 
-    fn log_energy(&mut self, cluster: &dynamics::Cluster) {
-        use dynamics::potentials;
+    fn log_energy(&mut self, cluster: &dynamics::Cluster, charges: &[f64], field: &Field) {
         let kinetic_energy = cluster.kinetic_energy();
-        let angular_momentum = cluster.angular_momentum();
-        let potential_energy = cluster.potential_energy(|points, i| {
-            points[i].mass * potentials::gravity(&points[i], points)
-        });
+        let angular_momentum = physics::angular_momentum(cluster);
+        let potential_energy = field.potential_energy(cluster, charges);
+        let linear_momentum = physics::linear_momentum(cluster);
+        let angular_momentum_vector = physics::angular_momentum_vector(cluster);
         let total_energy = kinetic_energy + potential_energy;
         self.energy_unit.rescale(&total_energy);
         self.buffer += &format!("\
@@ -209,13 +353,22 @@ simulated: {:?}",
 kinetic energy: {}
 potential energy: {}
 total energy: {}
-angular momentum: {:.10e}",
+angular momentum: {:.10e}
+angular momentum vector: ({:.10e}, {:.10e}, {:.10e})
+linear momentum vector: ({:.10e}, {:.10e}, {:.10e})",
                                 self.energy_unit.string_of(&kinetic_energy),
                                 self.energy_unit.string_of(&potential_energy),
                                 self.energy_unit.string_of(&total_energy),
-                                angular_momentum
+                                angular_momentum,
+                                angular_momentum_vector.x, angular_momentum_vector.y, angular_momentum_vector.z,
+                                linear_momentum.x, linear_momentum.y, linear_momentum.z,
         );
 
+        self.buffer += &match field.virial_ratio(cluster, charges) {
+            None => String::from("\nvirial ratio: undefined"),
+            Some(ratio) => format!("\nvirial ratio: {:.6}", ratio),
+        };
+
         let barycenter = cluster.barycenter();
         self.units.rescale(&barycenter.state);
         self.buffer += &format!("\n*** barycenter ***\n{}",
@@ -237,23 +390,37 @@ pub struct Units {
     pub time: Unit,
     pub distance: Unit,
     pub mass: Unit,
+    pub angle: Unit,
     pub speed: Compound,
     pub acceleration: Compound,
 }
 
 impl Units {
     pub fn new(distance: Unit, mass: Unit, time: Unit) -> Units {
+        use unitflow::suffix::*;
         let speed = distance.clone() / time.clone();
         let acceleration = speed.clone() / time.clone();
         Units {
             time,
             distance,
             mass,
+            angle: Unit::from(unitflow::Scale::from(Angle::Radians)),
             speed,
             acceleration,
         }
     }
 
+    pub fn toggle_angle(&mut self) -> &mut Self {
+        use unitflow::suffix::*;
+        let radians = Unit::from(unitflow::Scale::from(Angle::Radians));
+        self.angle = if self.angle == radians {
+            Unit::from(unitflow::Scale::from(Angle::Degrees))
+        } else {
+            radians
+        };
+        self
+    }
+
     pub fn default() -> Units {
         use unitflow::suffix::*;
         let time = Unit::from(unitflow::Scale::from(Time::Second));
@@ -1,35 +1,60 @@
 extern crate find_folder;
+extern crate gl;
 extern crate opengl_graphics;
 extern crate piston_window;
 
 use std::{env, process};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use dynamics;
 use dynamics::orbital;
 use opengl_graphics::OpenGL;
 use piston::event_loop::EventLoop;
-use piston::input::{Button, MouseCursorEvent, PressEvent, RenderEvent, UpdateEvent};
+use piston::input::{Button, MouseCursorEvent, MouseScrollEvent, PressEvent, RenderEvent, UpdateEvent};
 use piston_window::{PistonWindow, WindowSettings};
 
 use nbodies::App;
-use nbodies::common::Input;
+use nbodies::common::{HEADLESS_DT, Input};
 use nbodies::core::{Config, Simulator};
+use nbodies::record::Replay;
 
 fn main() {
     let config = Config::from_args(env::args().collect()).unwrap_or_else(|err| {
         eprintln!("Error during arguments parsing: {}", err);
         process::exit(1);
     });
-    let mut app = match &config.path {
-        None =>
-            App::new(Simulator::from(dynamics::Cluster::empty()), config),
-        Some(path) =>
-            App::from_orbital(orbital::Cluster::from_file(Path::new(path)).unwrap_or_else(|err| {
-                eprintln!("Error during cluster reading: {}", err);
-                process::exit(1);
-            }), config),
+    let mut app = if let Some(path) = &config.replay {
+        let replay = Replay::from_file(Path::new(path)).unwrap_or_else(|err| {
+            eprintln!("Error during replay reading: {}", err);
+            process::exit(1);
+        });
+        App::from_replay(replay, config)
+    } else {
+        match &config.path {
+            None => match config.bodies {
+                None => App::new(Simulator::from(dynamics::Cluster::empty()), config),
+                Some(count) => App::from_random(count, config),
+            },
+            Some(path) =>
+                App::from_orbital(orbital::Cluster::from_file(Path::new(path)).unwrap_or_else(|err| {
+                    eprintln!("Error during cluster reading: {}", err);
+                    process::exit(1);
+                }), config),
+        }
     };
+    if let Some(steps) = app.config.headless {
+        let (energy, diverged) = app.run_headless(steps, HEADLESS_DT);
+        if !energy.is_finite() {
+            eprintln!("Error during headless simulation: energy diverged to {}", energy);
+            process::exit(1);
+        }
+        if diverged > 0 {
+            eprintln!("Error during headless simulation: {} bodies diverged and were removed", diverged);
+            process::exit(1);
+        }
+        return;
+    }
     let mut input = Input::new();
     let mut window: PistonWindow =
         WindowSettings::new("Solar System Keeps Rollin'", app.config.size)
@@ -42,16 +67,22 @@ fn main() {
                 process::exit(1);
             });
 
-    window.events.set_max_fps(60);
-    window.events.set_ups(60);
+    gl::load_with(|symbol| window.window.ctx.get_proc_address(symbol) as *const _);
+    window.events.set_max_fps(app.config.fps);
+    window.events.set_ups(app.config.ups);
 
     let assets = find_folder::Search::ParentsThenKids(3, 3).for_folder("assets").unwrap();
     let mut glyphs = window.load_font(assets.join("FiraSans-Regular.ttf")).unwrap();
+    let mut screenshot = false;
     while let Some(event) = window.next() {
         event.mouse_cursor(|pos| {
             input.cursor = pos;
         });
 
+        if let Some(scroll) = event.mouse_scroll_args() {
+            app.on_scroll(&scroll, &input.cursor);
+        }
+
         if let Some(Button::Mouse(button)) = event.press_args() {
             input.button = Some(button);
             app.on_click(&button);
@@ -59,11 +90,20 @@ fn main() {
 
         if let Some(Button::Keyboard(key)) = event.press_args() {
             input.key = Some(key);
+            screenshot |= key == app.keys.screenshot;
             app.on_key(&key);
         }
 
         if let Some(_args) = event.render_args() {
             app.render(&input.cursor, &mut window, &event, &mut glyphs);
+            if screenshot {
+                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                let path = format!("screenshots/screenshot-{}.png", timestamp);
+                if let Err(err) = app.save_screenshot(Path::new(&path)) {
+                    eprintln!("Error during screenshot saving: {}", err);
+                }
+                screenshot = false;
+            }
             app.log(&input);
         }
         if let Some(args) = event.update_args() {
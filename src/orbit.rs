@@ -0,0 +1,152 @@
+use std::f64::consts::PI;
+
+use dynamics::orbital::{Body, Orbit};
+use geomath::matrix::Matrix3;
+use geomath::prelude::Metric;
+use geomath::prelude::transforms::Rotation3;
+use geomath::vector::Vector3;
+
+use crate::common::cross;
+
+const KEPLER_MAX_ITERATIONS: usize = 64;
+const KEPLER_TOLERANCE: f64 = 1e-12;
+const ASYMPTOTE_MARGIN: f64 = 1e-3;
+const CIRCULAR_TOLERANCE: f64 = 1e-9;
+
+pub trait Kepler {
+    fn from_state(position: &Vector3, speed: &Vector3, mu: f64) -> Self where Self: Sized;
+
+    fn eccentricity_of(&self) -> f64;
+
+    fn period(&self) -> f64;
+
+    fn is_hyperbolic(&self) -> bool;
+
+    fn anomaly_range(&self) -> (f64, f64);
+
+    fn true_anomaly_at_time(&self, t: f64, period: f64) -> f64;
+
+    fn inclination_rotation(&self) -> Matrix3;
+
+    fn inclined_position_at(&self, true_anomaly: f64) -> Vector3;
+
+    fn inclined_speed_at(&self, true_anomaly: f64) -> Vector3;
+}
+
+impl Kepler for Orbit {
+    fn from_state(position: &Vector3, speed: &Vector3, mu: f64) -> Orbit {
+        let mut orbit = Orbit::zeros();
+        let distance = position.magnitude();
+        if mu <= 0. || distance == 0. {
+            return orbit;
+        }
+        let momentum = cross(position, speed);
+        let energy = 0.5 * speed.magnitude().powi(2) - mu / distance;
+        let semi_major = if energy == 0. { std::f64::INFINITY } else { -0.5 * mu / energy };
+        orbit.mu = mu;
+        if momentum.magnitude() == 0. {
+            orbit.periapsis = 0.;
+            orbit.apoapsis = if energy < 0. { 2. * semi_major } else { std::f64::INFINITY };
+            orbit.argument = position.y.atan2(position.x);
+            return orbit;
+        }
+        let eccentricity_vector = cross(speed, &momentum) / mu - *position / distance;
+        let eccentricity = eccentricity_vector.magnitude();
+        let inclination = (momentum.z / momentum.magnitude()).max(-1.).min(1.).acos();
+        let node = if inclination < CIRCULAR_TOLERANCE { 0. } else { momentum.x.atan2(-momentum.y) };
+        let planar = Matrix3::from_rotation_z(node) * Matrix3::from_rotation_x(-inclination) * Matrix3::from_rotation_z(-node)
+            * eccentricity_vector;
+        orbit.periapsis = momentum.magnitude().powi(2) / (mu * (1. + eccentricity));
+        orbit.apoapsis = if energy == 0. { std::f64::INFINITY } else { semi_major * (1. + eccentricity) };
+        orbit.argument = if eccentricity < CIRCULAR_TOLERANCE { 0. } else { planar.y.atan2(planar.x) };
+        orbit.inclination.value = inclination;
+        orbit.inclination.argument = node;
+        orbit
+    }
+
+    fn eccentricity_of(&self) -> f64 {
+        if !self.apoapsis.is_finite() {
+            return 1.;
+        }
+        let sum = self.apoapsis + self.periapsis;
+        if sum == 0. {
+            return 0.;
+        }
+        (self.apoapsis - self.periapsis) / sum
+    }
+
+    fn period(&self) -> f64 {
+        let semi_major = self.semi_major();
+        if self.mu <= 0. || !(semi_major > 0.) || !semi_major.is_finite() {
+            return std::f64::INFINITY;
+        }
+        2. * PI * (semi_major.powi(3) / self.mu).sqrt()
+    }
+
+    fn is_hyperbolic(&self) -> bool {
+        self.eccentricity_of() >= 1.
+    }
+
+    fn anomaly_range(&self) -> (f64, f64) {
+        if !self.is_hyperbolic() {
+            return (0., 2. * PI);
+        }
+        let asymptote = (-1. / self.eccentricity_of()).acos() - ASYMPTOTE_MARGIN;
+        (-asymptote, asymptote)
+    }
+
+    fn true_anomaly_at_time(&self, t: f64, period: f64) -> f64 {
+        if !period.is_finite() || period <= 0. {
+            return 0.;
+        }
+        let eccentricity = self.eccentricity_of().min(1. - KEPLER_TOLERANCE);
+        let mean_anomaly = 2. * PI * (t / period).rem_euclid(1.);
+        let mut anomaly = if eccentricity > 0.8 { PI } else { mean_anomaly };
+        let mut delta;
+        for _ in 0..KEPLER_MAX_ITERATIONS {
+            delta = (anomaly - eccentricity * anomaly.sin() - mean_anomaly) / (1. - eccentricity * anomaly.cos());
+            anomaly -= delta;
+            if delta.abs() < KEPLER_TOLERANCE {
+                break;
+            }
+        }
+        if !anomaly.is_finite() {
+            anomaly = mean_anomaly;
+        }
+        2. * ((1. + eccentricity).sqrt() * (0.5 * anomaly).sin())
+            .atan2((1. - eccentricity).sqrt() * (0.5 * anomaly).cos())
+    }
+
+    fn inclination_rotation(&self) -> Matrix3 {
+        let node = self.inclination.argument;
+        Matrix3::from_rotation_z(node) * Matrix3::from_rotation_x(self.inclination.value) * Matrix3::from_rotation_z(-node)
+    }
+
+    fn inclined_position_at(&self, true_anomaly: f64) -> Vector3 {
+        if self.inclination.value == 0. {
+            return self.position_at(true_anomaly);
+        }
+        self.inclination_rotation() * self.position_at(true_anomaly)
+    }
+
+    fn inclined_speed_at(&self, true_anomaly: f64) -> Vector3 {
+        if self.inclination.value == 0. {
+            return self.speed_at(true_anomaly);
+        }
+        self.inclination_rotation() * self.speed_at(true_anomaly)
+    }
+}
+
+pub trait Influence {
+    fn sphere_of_influence(&self, primary_mass: f64) -> f64;
+}
+
+impl Influence for Body {
+    fn sphere_of_influence(&self, primary_mass: f64) -> f64 {
+        let semi_major = self.orbit.semi_major();
+        if primary_mass <= 0. || !(semi_major > 0.) || !semi_major.is_finite() {
+            return 0.;
+        }
+        semi_major * (self.mass / primary_mass).powf(0.4)
+    }
+}
@@ -0,0 +1,439 @@
+use dynamics::Cluster;
+use dynamics::point::Point3;
+use geomath::prelude::Metric;
+use geomath::vector;
+use geomath::vector::{vec3, vec6, Vector3, Vector6};
+
+use crate::common::cross;
+
+pub const G_UNIV: f64 = 6.67408e-11;
+pub const SPEED_OF_LIGHT: f64 = 299792458.;
+pub const K_COULOMB: f64 = 8.9875517923e9;
+
+const OCTREE_MAX_DEPTH: usize = 32;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Halo {
+    pub velocity: f64,
+    pub core_radius: f64,
+}
+
+impl Halo {
+    #[inline]
+    pub fn acceleration(&self, position: &Vector3) -> Vector3 {
+        let distance2 = position.magnitude().powi(2) + self.core_radius * self.core_radius;
+        *position * (-self.velocity * self.velocity / distance2)
+    }
+
+    #[inline]
+    pub fn potential_at(&self, position: &Vector3) -> f64 {
+        let distance2 = position.magnitude().powi(2) + self.core_radius * self.core_radius;
+        0.5 * self.velocity * self.velocity * distance2.ln()
+    }
+
+    #[inline]
+    pub fn point_potential_energy(&self, point: &Point3) -> f64 {
+        point.mass * self.potential_at(&point.state.position)
+    }
+
+    pub fn potential_energy(&self, cluster: &Cluster) -> f64 {
+        cluster.points.iter().map(|point| self.point_potential_energy(point)).sum()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Field {
+    pub gravity: f64,
+    pub drag: Option<f64>,
+    pub spring: Option<f64>,
+    pub halo: Option<Halo>,
+    pub relativistic: bool,
+    pub coulomb: bool,
+}
+
+impl Field {
+    pub fn new(gravity: f64) -> Field {
+        Field {
+            gravity,
+            drag: None,
+            spring: None,
+            halo: None,
+            relativistic: false,
+            coulomb: false,
+        }
+    }
+
+    pub fn potential_energy(&self, cluster: &Cluster, charges: &[f64]) -> f64 {
+        let mut energy = match self.spring {
+            Some(stiffness) => spring_potential_energy(cluster, stiffness),
+            None => potential_energy(cluster, self.gravity),
+        };
+        if self.coulomb {
+            energy += coulomb_potential_energy(&cluster.points, charges, K_COULOMB);
+        }
+        match self.halo {
+            Some(halo) => energy + halo.potential_energy(cluster),
+            None => energy,
+        }
+    }
+
+    pub fn point_potential_energy(&self, points: &Vec<Point3>, charges: &[f64], i: usize) -> f64 {
+        use dynamics::potentials;
+        let point = &points[i];
+        let mut energy = match self.spring {
+            Some(stiffness) => 0.5 * stiffness * point.mass * point.state.position.magnitude().powi(2),
+            None => point.mass * potentials::gravity(point, points) * self.gravity / G_UNIV,
+        };
+        if self.coulomb {
+            energy += point_coulomb_energy(point, charges[i], points, charges, K_COULOMB);
+        }
+        match self.halo {
+            Some(halo) => energy + halo.point_potential_energy(point),
+            None => energy,
+        }
+    }
+
+    pub fn acceleration(&self, point: &Point3, charge: f64, points: &Vec<Point3>, charges: &[f64], epsilon: f64) -> Vector3 {
+        let acceleration = match self.spring {
+            Some(stiffness) => spring(&point.state.position, stiffness),
+            None => gravity_acceleration(point, points, self.gravity, epsilon),
+        };
+        acceleration + self.perturbation(point, charge, points, charges)
+    }
+
+    pub fn perturbation(&self, point: &Point3, charge: f64, points: &Vec<Point3>, charges: &[f64]) -> Vector3 {
+        let mut acceleration = vector::consts::ZEROS_3;
+        if self.coulomb {
+            acceleration += coulomb_acceleration(point, charge, points, charges, K_COULOMB);
+        }
+        if self.relativistic && self.spring.is_none() {
+            acceleration += relativistic_correction(point, points, self.gravity);
+        }
+        if let Some(halo) = self.halo {
+            acceleration += halo.acceleration(&point.state.position);
+        }
+        if let Some(coefficient) = self.drag {
+            acceleration += drag(&point.state.speed, coefficient);
+        }
+        acceleration
+    }
+
+    pub fn potential_at(&self, position: &Vector3, points: &Vec<Point3>, epsilon: f64) -> f64 {
+        let potential = match self.spring {
+            Some(stiffness) => 0.5 * stiffness * position.magnitude().powi(2),
+            None => points.iter()
+                .map(|point| {
+                    let distance = ((*position - point.state.position).magnitude().powi(2) + epsilon * epsilon).sqrt();
+                    if distance == 0. { 0. } else { -self.gravity * point.mass / distance }
+                })
+                .sum(),
+        };
+        match self.halo {
+            Some(halo) => potential + halo.potential_at(position),
+            None => potential,
+        }
+    }
+
+    #[inline]
+    pub fn total_energy(&self, cluster: &Cluster, charges: &[f64]) -> f64 {
+        cluster.kinetic_energy() + self.potential_energy(cluster, charges)
+    }
+
+    pub fn virial_ratio(&self, cluster: &Cluster, charges: &[f64]) -> Option<f64> {
+        if self.spring.is_some() || self.halo.is_some() {
+            return None;
+        }
+        let mut energy = potential_energy(cluster, self.gravity);
+        if self.coulomb {
+            energy += coulomb_potential_energy(&cluster.points, charges, K_COULOMB);
+        }
+        if energy.abs() < std::f64::MIN_POSITIVE {
+            return None;
+        }
+        Some(2. * cluster.kinetic_energy() / energy.abs())
+    }
+}
+
+#[inline]
+pub fn derivative(speed: &Vector3, acceleration: &Vector3) -> Vector6 {
+    vec6(speed.x, speed.y, speed.z, acceleration.x, acceleration.y, acceleration.z)
+}
+
+#[inline]
+fn attraction(position: &Vector3, other: &Vector3, mass: f64, g: f64, epsilon: f64) -> Vector3 {
+    let direction = *other - *position;
+    let distance = (direction.magnitude().powi(2) + epsilon * epsilon).sqrt();
+    direction * (g * mass / distance.powi(3))
+}
+
+pub fn potential_energy(cluster: &Cluster, g: f64) -> f64 {
+    use dynamics::potentials;
+    let ratio = g / G_UNIV;
+    cluster.potential_energy(|points, i| {
+        points[i].mass * potentials::gravity(&points[i], points) * ratio
+    })
+}
+
+pub fn spring_potential_energy(cluster: &Cluster, stiffness: f64) -> f64 {
+    cluster.points.iter()
+        .map(|point| 0.5 * stiffness * point.mass * point.state.position.magnitude().powi(2))
+        .sum()
+}
+
+pub fn linear_momentum(cluster: &Cluster) -> Vector3 {
+    let mut momentum = vector::consts::ZEROS_3;
+    for point in cluster.points.iter() {
+        momentum += point.state.speed * point.mass;
+    }
+    momentum
+}
+
+pub fn angular_momentum_vector(cluster: &Cluster) -> Vector3 {
+    let mut momentum = vector::consts::ZEROS_3;
+    if cluster.points.is_empty() {
+        return momentum;
+    }
+    let barycenter = cluster.barycenter();
+    for point in cluster.points.iter() {
+        let position = point.state.position - barycenter.state.position;
+        let speed = point.state.speed - barycenter.state.speed;
+        momentum += cross(&position, &speed) * point.mass;
+    }
+    momentum
+}
+
+#[inline]
+pub fn angular_momentum(cluster: &Cluster) -> f64 {
+    angular_momentum_vector(cluster).magnitude()
+}
+
+#[inline]
+pub fn spring(position: &Vector3, stiffness: f64) -> Vector3 {
+    *position * -stiffness
+}
+
+#[inline]
+pub fn drag(speed: &Vector3, coefficient: f64) -> Vector3 {
+    *speed * (-coefficient * speed.magnitude())
+}
+
+pub fn gravity_acceleration(point: &Point3, points: &Vec<Point3>, g: f64, epsilon: f64) -> Vector3 {
+    let mut acceleration = vector::consts::ZEROS_3;
+    for other in points.iter() {
+        if std::ptr::eq(point, other) {
+            continue;
+        }
+        acceleration += attraction(&point.state.position, &other.state.position, other.mass, g, epsilon);
+    }
+    acceleration
+}
+
+pub fn gravity_all(points: &Vec<Point3>, g: f64, epsilon: f64) -> Vec<Vector3> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        points.par_iter().map(|point| gravity_acceleration(point, points, g, epsilon)).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        points.iter().map(|point| gravity_acceleration(point, points, g, epsilon)).collect()
+    }
+}
+
+pub fn coulomb_acceleration(point: &Point3, charge: f64, points: &Vec<Point3>, charges: &[f64], k: f64) -> Vector3 {
+    let mut acceleration = vector::consts::ZEROS_3;
+    if charge == 0. || point.mass == 0. {
+        return acceleration;
+    }
+    for (other, other_charge) in points.iter().zip(charges.iter()) {
+        if std::ptr::eq(point, other) || *other_charge == 0. {
+            continue;
+        }
+        let direction = point.state.position - other.state.position;
+        let distance = direction.magnitude();
+        if distance == 0. {
+            continue;
+        }
+        acceleration += direction * (k * charge * other_charge / (point.mass * distance.powi(3)));
+    }
+    acceleration
+}
+
+pub fn point_coulomb_energy(point: &Point3, charge: f64, points: &Vec<Point3>, charges: &[f64], k: f64) -> f64 {
+    if charge == 0. {
+        return 0.;
+    }
+    points.iter().zip(charges.iter())
+        .filter(|(other, other_charge)| !std::ptr::eq(point, *other) && **other_charge != 0.)
+        .map(|(other, other_charge)| {
+            let distance = point.state.position % other.state.position;
+            if distance == 0. { 0. } else { k * charge * other_charge / distance }
+        })
+        .sum()
+}
+
+pub fn coulomb_potential_energy(points: &Vec<Point3>, charges: &[f64], k: f64) -> f64 {
+    let mut energy = 0.;
+    for i in 0..points.len().min(charges.len()) {
+        for j in (i + 1)..points.len().min(charges.len()) {
+            let distance = points[i].state.position % points[j].state.position;
+            if distance > 0. {
+                energy += k * charges[i] * charges[j] / distance;
+            }
+        }
+    }
+    energy
+}
+
+pub fn relativistic_correction(point: &Point3, points: &Vec<Point3>, g: f64) -> Vector3 {
+    let mut acceleration = vector::consts::ZEROS_3;
+    for other in points.iter() {
+        if std::ptr::eq(point, other) {
+            continue;
+        }
+        let direction = other.state.position - point.state.position;
+        let distance = direction.magnitude();
+        if distance == 0. {
+            continue;
+        }
+        let momentum = cross(&direction, &(other.state.speed - point.state.speed)).magnitude();
+        let factor = 3. * momentum * momentum / (SPEED_OF_LIGHT * SPEED_OF_LIGHT * distance * distance);
+        acceleration += direction * (g * other.mass * factor / distance.powi(3));
+    }
+    acceleration
+}
+
+struct Node {
+    center: Vector3,
+    size: f64,
+    mass: f64,
+    moment: Vector3,
+    bodies: Vec<usize>,
+    children: Option<[usize; 8]>,
+}
+
+impl Node {
+    fn new(center: Vector3, size: f64) -> Node {
+        Node {
+            center,
+            size,
+            mass: 0.,
+            moment: vector::consts::ZEROS_3,
+            bodies: vec![],
+            children: None,
+        }
+    }
+}
+
+pub struct Octree {
+    nodes: Vec<Node>,
+}
+
+impl Octree {
+    pub fn new(points: &Vec<Point3>) -> Octree {
+        let mut min = vector::consts::ZEROS_3;
+        let mut max = vector::consts::ZEROS_3;
+        if let Some(first) = points.first() {
+            min = first.state.position;
+            max = first.state.position;
+        }
+        for point in points.iter() {
+            let position = &point.state.position;
+            min = vec3(min.x.min(position.x), min.y.min(position.y), min.z.min(position.z));
+            max = vec3(max.x.max(position.x), max.y.max(position.y), max.z.max(position.z));
+        }
+        let size = (max.x - min.x).max(max.y - min.y).max(max.z - min.z);
+        let mut ret = Octree {
+            nodes: vec![Node::new((min + max) * 0.5, size)],
+        };
+        for i in 0..points.len() {
+            ret.insert(0, i, points, 0);
+        }
+        ret
+    }
+
+    pub fn acceleration(&self, points: &Vec<Point3>, i: usize, theta: f64, g: f64, epsilon: f64) -> Vector3 {
+        let position = points[i].state.position;
+        let mut acceleration = vector::consts::ZEROS_3;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.mass == 0. {
+                continue;
+            }
+            match node.children {
+                None => for &j in node.bodies.iter() {
+                    if j != i {
+                        acceleration += attraction(&position, &points[j].state.position, points[j].mass, g, epsilon);
+                    }
+                },
+                Some(children) => {
+                    let barycenter = node.moment / node.mass;
+                    if node.size < theta * (barycenter % position) {
+                        acceleration += attraction(&position, &barycenter, node.mass, g, epsilon);
+                    } else {
+                        stack.extend(children.iter());
+                    }
+                }
+            }
+        }
+        acceleration
+    }
+
+    pub fn acceleration_all(&self, points: &Vec<Point3>, theta: f64, g: f64, epsilon: f64) -> Vec<Vector3> {
+        (0..points.len()).map(|i| self.acceleration(points, i, theta, g, epsilon)).collect()
+    }
+
+    fn insert(&mut self, index: usize, i: usize, points: &Vec<Point3>, depth: usize) {
+        let position = points[i].state.position;
+        let mass = points[i].mass;
+        self.nodes[index].mass += mass;
+        self.nodes[index].moment += position * mass;
+        if self.nodes[index].children.is_none() {
+            if self.nodes[index].bodies.is_empty() || depth == OCTREE_MAX_DEPTH {
+                self.nodes[index].bodies.push(i);
+                return;
+            }
+            self.split(index);
+            let bodies = std::mem::replace(&mut self.nodes[index].bodies, vec![]);
+            for body in bodies {
+                let child = self.octant(index, &points[body].state.position);
+                self.insert(child, body, points, depth + 1);
+            }
+        }
+        let child = self.octant(index, &position);
+        self.insert(child, i, points, depth + 1);
+    }
+
+    fn split(&mut self, index: usize) {
+        let center = self.nodes[index].center;
+        let size = self.nodes[index].size * 0.5;
+        let quarter = size * 0.5;
+        let mut children = [0; 8];
+        for k in 0..8 {
+            let offset = vec3(
+                if k & 1 == 0 { -quarter } else { quarter },
+                if k & 2 == 0 { -quarter } else { quarter },
+                if k & 4 == 0 { -quarter } else { quarter },
+            );
+            children[k] = self.nodes.len();
+            self.nodes.push(Node::new(center + offset, size));
+        }
+        self.nodes[index].children = Some(children);
+    }
+
+    fn octant(&self, index: usize, position: &Vector3) -> usize {
+        let node = &self.nodes[index];
+        let mut k = 0;
+        if position.x > node.center.x {
+            k |= 1;
+        }
+        if position.y > node.center.y {
+            k |= 2;
+        }
+        if position.z > node.center.z {
+            k |= 4;
+        }
+        node.children.unwrap()[k]
+    }
+}
@@ -0,0 +1,157 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::core::{Simulator, Snapshot};
+
+const RECORD_FLUSH_ROWS: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Csv,
+    Frames,
+}
+
+pub struct Recorder {
+    writer: Option<BufWriter<File>>,
+    format: Format,
+    bodies: usize,
+    rows: usize,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder {
+            writer: None,
+            format: Format::Csv,
+            bodies: 0,
+            rows: 0,
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    #[inline]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn enable(&mut self, path: &Path, format: Format, simulator: &Simulator) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        if format == Format::Csv {
+            let mut header = String::from("time");
+            for i in 0..simulator.cluster.len() {
+                header += &format!(",x{0},y{0},z{0},vx{0},vy{0},vz{0}", i);
+            }
+            writeln!(writer, "{}", header)?;
+        }
+        self.writer = Some(writer);
+        self.format = format;
+        self.bodies = simulator.cluster.len();
+        self.rows = 0;
+        Ok(())
+    }
+
+    pub fn disable(&mut self) -> io::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, time: f64, simulator: &Simulator) -> io::Result<()> {
+        let writer = match self.writer.as_mut() {
+            None => return Ok(()),
+            Some(writer) => writer,
+        };
+        let row = match self.format {
+            Format::Csv => {
+                if simulator.cluster.len() != self.bodies {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("body count changed from {} to {}", self.bodies, simulator.cluster.len()),
+                    ));
+                }
+                let mut row = format!("{:e}", time);
+                for point in simulator.cluster.points.iter() {
+                    let position = &point.state.position;
+                    let speed = &point.state.speed;
+                    row += &format!(
+                        ",{:e},{:e},{:e},{:e},{:e},{:e}",
+                        position.x, position.y, position.z, speed.x, speed.y, speed.z
+                    );
+                }
+                row
+            }
+            Format::Frames => serde_json::to_string(&simulator.snapshot())?,
+        };
+        writeln!(writer, "{}", row)?;
+        self.rows += 1;
+        if self.rows % RECORD_FLUSH_ROWS == 0 {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Replay {
+    frames: Vec<Snapshot>,
+    index: usize,
+}
+
+impl Replay {
+    pub fn from_file(path: &Path) -> Result<Replay, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read replay file {}: {}", path.display(), err))?;
+        let mut frames = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let frame: Snapshot = serde_json::from_str(line)
+                .map_err(|err| format!("invalid frame {} in replay file {}: {}", i + 1, path.display(), err))?;
+            let len = frame.masses.len();
+            if frame.positions.len() != len || frame.speeds.len() != len || frame.bodies.len() != len {
+                return Err(format!(
+                    "inconsistent frame {} in replay file {}: {} masses, {} positions, {} speeds, {} bodies",
+                    i + 1, path.display(), len, frame.positions.len(), frame.speeds.len(), frame.bodies.len()
+                ).into());
+            }
+            frames.push(frame);
+        }
+        if frames.is_empty() {
+            return Err(format!("empty replay file {}", path.display()).into());
+        }
+        Ok(Replay { frames, index: 0 })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    #[inline]
+    pub fn first(&self) -> &Snapshot {
+        &self.frames[0]
+    }
+
+    pub fn next(&mut self) -> Option<&Snapshot> {
+        let frame = self.frames.get(self.index)?;
+        self.index += 1;
+        Some(frame)
+    }
+}
@@ -0,0 +1,42 @@
+use geomath::vector::consts::{EX_3, EY_3, EZ_3};
+use geomath::vector::{vec2, Vector3};
+use nbodies::common::{cross, cross_z, lerp, lerp_clamped};
+
+fn assert_close(lhs: &Vector3, rhs: &Vector3) {
+    assert!((lhs.x - rhs.x).abs() < 1e-12 && (lhs.y - rhs.y).abs() < 1e-12 && (lhs.z - rhs.z).abs() < 1e-12);
+}
+
+#[test]
+fn cross_of_basis_vectors() {
+    assert_close(&cross(&EX_3, &EY_3), &EZ_3);
+    assert_close(&cross(&EY_3, &EZ_3), &EX_3);
+    assert_close(&cross(&EZ_3, &EX_3), &EY_3);
+    assert_close(&cross(&EY_3, &EX_3), &(EZ_3 * -1.));
+}
+
+#[test]
+fn cross_z_of_basis_vectors() {
+    let ex = vec2(1., 0.);
+    let ey = vec2(0., 1.);
+    assert_eq!(cross_z(&ex, &ey), 1.);
+    assert_eq!(cross_z(&ey, &ex), -1.);
+    assert_eq!(cross_z(&ex, &ex), 0.);
+}
+
+#[test]
+fn lerp_endpoints_and_midpoint() {
+    let from = vec2(1., -2.);
+    let to = vec2(3., 6.);
+    assert_eq!(lerp(from, to, 0.).x, 1.);
+    assert_eq!(lerp(from, to, 1.).y, 6.);
+    assert_eq!(lerp(from, to, 0.5).x, 2.);
+    assert_eq!(lerp(from, to, 0.5).y, 2.);
+    assert_eq!(lerp(1., 3., 2.), 5.);
+}
+
+#[test]
+fn lerp_clamped_stays_between_endpoints() {
+    assert_eq!(lerp_clamped(1., 3., 2.), 3.);
+    assert_eq!(lerp_clamped(1., 3., -1.), 1.);
+    assert_eq!(lerp_clamped(1., 3., 0.25), 1.5);
+}
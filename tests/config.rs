@@ -0,0 +1,39 @@
+use std::env;
+use std::fs;
+
+use nbodies::common::Palette;
+use nbodies::core::Config;
+
+#[test]
+fn save_then_load_round_trip() {
+    let path = env::temp_dir().join("nbodies-config-round-trip.toml");
+    let mut config = Config::default();
+    config.scale.time = 3600.;
+    config.oversampling = 16;
+    config.trajectory_length = 512;
+    config.pause = true;
+    config.palette = Some(Palette::light());
+    config.save(&path).unwrap();
+    let loaded = Config::from_file(&path).unwrap();
+    fs::remove_file(&path).ok();
+    assert_eq!(loaded.scale.time, 3600.);
+    assert_eq!(loaded.scale.distance, config.scale.distance);
+    assert_eq!(loaded.oversampling, 16);
+    assert_eq!(loaded.trajectory_length, 512);
+    assert!(loaded.pause);
+    assert_eq!(loaded.palette.unwrap().background, Palette::light().background);
+}
+
+fn args(options: &[&str]) -> Vec<String> {
+    let mut args = vec![String::from("nbodies")];
+    args.extend(options.iter().map(|option| option.to_string()));
+    args
+}
+
+#[test]
+fn snap_spacing_must_be_positive() {
+    assert_eq!(Config::from_args(args(&["--snap-spacing", "1e9"])).unwrap().snap_spacing, Some(1e9));
+    assert!(Config::from_args(args(&["--snap-spacing", "0"])).is_err());
+    assert!(Config::from_args(args(&["--snap-spacing", "-1e9"])).is_err());
+    assert!(Config::from_args(args(&["--snap-spacing", "NaN"])).is_err());
+}
@@ -0,0 +1,51 @@
+use dynamics::point::Point3;
+use geomath::vector::vec3;
+
+use nbodies::physics;
+use nbodies::physics::{Field, G_UNIV, K_COULOMB};
+
+fn pair() -> Vec<Point3> {
+    vec![
+        Point3::inertial(vec3(0., 0., 0.), vec3(0., 0., 0.), 1.),
+        Point3::inertial(vec3(1., 0., 0.), vec3(0., 0., 0.), 1.),
+    ]
+}
+
+#[test]
+fn zero_charges_match_gravity() {
+    let points = pair();
+    let charges = vec![0., 0.];
+    let mut field = Field::new(G_UNIV);
+    let gravity = field.acceleration(&points[0], charges[0], &points, &charges, 0.);
+    field.coulomb = true;
+    let acceleration = field.acceleration(&points[0], charges[0], &points, &charges, 0.);
+    assert_eq!(acceleration.x, gravity.x);
+    assert_eq!(acceleration.y, gravity.y);
+    assert_eq!(acceleration.z, gravity.z);
+}
+
+#[test]
+fn like_charges_repel() {
+    let points = pair();
+    let charges = vec![1e-6, 2e-6];
+    let acceleration = physics::coulomb_acceleration(&points[0], charges[0], &points, &charges, K_COULOMB);
+    assert!(acceleration.x < 0.);
+    assert!((acceleration.x + K_COULOMB * 2e-12).abs() < 1e-12);
+}
+
+#[test]
+fn opposite_charges_attract() {
+    let points = pair();
+    let charges = vec![1e-6, -2e-6];
+    let acceleration = physics::coulomb_acceleration(&points[0], charges[0], &points, &charges, K_COULOMB);
+    assert!(acceleration.x > 0.);
+}
+
+#[test]
+fn potential_energy_includes_coulomb() {
+    let points = pair();
+    let charges = vec![1e-6, 2e-6];
+    let energy = physics::coulomb_potential_energy(&points, &charges, K_COULOMB);
+    assert!((energy - K_COULOMB * 2e-12).abs() < 1e-12);
+    assert_eq!(physics::coulomb_potential_energy(&points, &[0., 0.], K_COULOMB), 0.);
+}
@@ -0,0 +1,18 @@
+use dynamics::orbital::Kind;
+
+use nbodies::draw::scaled_radius;
+
+#[test]
+fn star_is_drawn_larger_than_terrestrial() {
+    let sun = scaled_radius(&Kind::Star, 6.957e8);
+    let earth = scaled_radius(&Kind::Terrestrial, 6.371e6);
+    let mercury = scaled_radius(&Kind::Terrestrial, 2.44e6);
+    assert!(sun > earth);
+    assert!(earth > mercury);
+}
+
+#[test]
+fn scaled_radius_keeps_kind_base() {
+    assert_eq!(scaled_radius(&Kind::Terrestrial, 1.), scaled_radius(&Kind::Terrestrial, 0.));
+    assert!(scaled_radius(&Kind::Giant, 1.) > scaled_radius(&Kind::Terrestrial, 1.));
+}
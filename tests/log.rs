@@ -0,0 +1,15 @@
+use std::f64::consts::PI;
+
+use unitflow::Serialize;
+
+use nbodies::log::Units;
+
+#[test]
+fn toggle_angle_converts_pi_to_180_degrees() {
+    let mut units = Units::default();
+    assert!(units.angle.string_of(&PI).contains("3.14"));
+    units.toggle_angle();
+    assert!(units.angle.string_of(&PI).contains("180"));
+    units.toggle_angle();
+    assert!(units.angle.string_of(&PI).contains("3.14"));
+}
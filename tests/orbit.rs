@@ -0,0 +1,44 @@
+use geomath::vector::vec3;
+
+use nbodies::core::{ClusterBuilder, Simulator};
+use nbodies::physics::G_UNIV;
+
+const STAR_MASS: f64 = 1.989e30;
+const PLANET_MASS: f64 = 5.972e24;
+const MOON_MASS: f64 = 7.342e22;
+const PLANET_DISTANCE: f64 = 1.496e11;
+const MOON_DISTANCE: f64 = 3.844e8;
+const MOON_INCLINATION: f64 = 0.5;
+
+fn inclined_moon() -> Simulator {
+    let planet_speed = (G_UNIV * STAR_MASS / PLANET_DISTANCE).sqrt();
+    let moon_speed = (G_UNIV * PLANET_MASS / MOON_DISTANCE).sqrt();
+    let planet = vec3(PLANET_DISTANCE, 0., 0.);
+    let planet_velocity = vec3(0., planet_speed, 0.);
+    ClusterBuilder::new()
+        .add_body(STAR_MASS, vec3(0., 0., 0.), vec3(0., 0., 0.))
+        .add_body(PLANET_MASS, planet, planet_velocity)
+        .add_body(
+            MOON_MASS,
+            planet + vec3(MOON_DISTANCE, 0., 0.),
+            planet_velocity + vec3(0., MOON_INCLINATION.cos(), MOON_INCLINATION.sin()) * moon_speed,
+        )
+        .build()
+}
+
+#[test]
+fn moon_orbit_is_relative_to_its_planet() {
+    let simulator = inclined_moon();
+    let (primary, orbit) = simulator.relative_orbit(2).unwrap();
+    assert_eq!(primary, 1);
+    assert!((orbit.periapsis - MOON_DISTANCE).abs() / MOON_DISTANCE < 1e-9);
+    assert!((orbit.apoapsis - MOON_DISTANCE).abs() / MOON_DISTANCE < 1e-9);
+    assert!((orbit.inclination.value - MOON_INCLINATION).abs() < 1e-9);
+    assert!(orbit.inclination.argument.abs() < 1e-9);
+}
+
+#[test]
+fn top_level_primary_has_no_relative_orbit() {
+    let simulator = inclined_moon();
+    assert!(simulator.relative_orbit(0).is_none());
+}
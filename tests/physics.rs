@@ -0,0 +1,97 @@
+use geomath::prelude::Metric;
+use geomath::vector::{vec3, Vector3};
+
+use nbodies::core::{ClusterBuilder, Config};
+use nbodies::physics;
+use nbodies::physics::{Field, G_UNIV, Octree};
+
+const PRIMARY_MASS: f64 = 5.972e24;
+const SATELLITE_MASS: f64 = 1.;
+const RADIUS: f64 = 7e6;
+
+fn circular_momentum(offset: Vector3, drift: Vector3) -> f64 {
+    let speed = (G_UNIV * PRIMARY_MASS / RADIUS).sqrt();
+    let simulator = ClusterBuilder::new()
+        .add_body(PRIMARY_MASS, offset, drift)
+        .add_body(SATELLITE_MASS, offset + vec3(RADIUS, 0., 0.), drift + vec3(0., speed, 0.))
+        .build();
+    physics::angular_momentum(&simulator.cluster)
+}
+
+#[test]
+fn angular_momentum_of_circular_orbit() {
+    let speed = (G_UNIV * PRIMARY_MASS / RADIUS).sqrt();
+    let expected = SATELLITE_MASS * speed * RADIUS;
+    let momentum = circular_momentum(vec3(0., 0., 0.), vec3(0., 0., 0.));
+    assert!((momentum - expected).abs() / expected < 1e-12);
+}
+
+#[test]
+fn angular_momentum_is_taken_about_the_barycenter() {
+    let speed = (G_UNIV * PRIMARY_MASS / RADIUS).sqrt();
+    let expected = SATELLITE_MASS * speed * RADIUS;
+    let momentum = circular_momentum(vec3(1e9, -2e9, 3e8), vec3(1e3, 2e3, -5e2));
+    assert!((momentum - expected).abs() / expected < 1e-6);
+}
+
+#[test]
+fn circular_orbit_is_virialized() {
+    let speed = (G_UNIV * PRIMARY_MASS / RADIUS).sqrt();
+    let simulator = ClusterBuilder::new()
+        .add_body(PRIMARY_MASS, vec3(0., 0., 0.), vec3(0., 0., 0.))
+        .add_body(SATELLITE_MASS, vec3(RADIUS, 0., 0.), vec3(0., speed, 0.))
+        .build();
+    let charges = vec![0., 0.];
+    let mut field = Field::new(G_UNIV);
+    let ratio = field.virial_ratio(&simulator.cluster, &charges).unwrap();
+    assert!((ratio - 1.).abs() < 1e-6);
+    field.spring = Some(1e-12);
+    assert!(field.virial_ratio(&simulator.cluster, &charges).is_none());
+}
+
+#[test]
+fn batched_gravity_matches_direct_sum() {
+    let simulator = ClusterBuilder::new()
+        .add_body(PRIMARY_MASS, vec3(0., 0., 0.), vec3(0., 0., 0.))
+        .add_body(SATELLITE_MASS, vec3(RADIUS, 0., 0.), vec3(0., 0., 0.))
+        .add_body(2. * PRIMARY_MASS, vec3(-RADIUS, 3. * RADIUS, 1e5), vec3(0., 0., 0.))
+        .build();
+    let points = &simulator.cluster.points;
+    let batch = physics::gravity_all(points, G_UNIV, 0.);
+    for i in 0..points.len() {
+        let direct = physics::gravity_acceleration(&points[i], points, G_UNIV, 0.);
+        assert_eq!(batch[i].x, direct.x);
+        assert_eq!(batch[i].y, direct.y);
+        assert_eq!(batch[i].z, direct.z);
+    }
+}
+
+#[test]
+fn barnes_hut_without_opening_matches_direct_sum() {
+    let simulator = ClusterBuilder::new()
+        .add_body(PRIMARY_MASS, vec3(0., 0., 0.), vec3(0., 0., 0.))
+        .add_body(SATELLITE_MASS, vec3(RADIUS, 0., 0.), vec3(0., 0., 0.))
+        .add_body(2. * PRIMARY_MASS, vec3(-RADIUS, 3. * RADIUS, 1e5), vec3(0., 0., 0.))
+        .add_body(PRIMARY_MASS, vec3(5. * RADIUS, -RADIUS, -2. * RADIUS), vec3(0., 0., 0.))
+        .build();
+    let points = &simulator.cluster.points;
+    let tree = Octree::new(points).acceleration_all(points, 0., G_UNIV, 0.);
+    let direct = physics::gravity_all(points, G_UNIV, 0.);
+    for i in 0..points.len() {
+        assert!((tree[i] - direct[i]).magnitude() <= 1e-12 * direct[i].magnitude());
+    }
+}
+
+#[test]
+fn doubling_gravity_doubles_the_acceleration() {
+    let simulator = ClusterBuilder::new()
+        .add_body(PRIMARY_MASS, vec3(0., 0., 0.), vec3(0., 0., 0.))
+        .add_body(SATELLITE_MASS, vec3(RADIUS, 0., 0.), vec3(0., 0., 0.))
+        .build();
+    let points = &simulator.cluster.points;
+    let single = physics::gravity_acceleration(&points[1], points, G_UNIV, 0.);
+    let double = physics::gravity_acceleration(&points[1], points, 2. * G_UNIV, 0.);
+    assert_eq!(double.x, 2. * single.x);
+    assert!(single.x < 0.);
+    assert_eq!(Config::default().field().gravity, G_UNIV);
+}
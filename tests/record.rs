@@ -0,0 +1,34 @@
+use std::env;
+use std::fs;
+
+use geomath::vector::vec3;
+
+use nbodies::core::ClusterBuilder;
+use nbodies::record::Replay;
+
+fn write_frames(name: &str, truncate: bool) -> Result<usize, String> {
+    let simulator = ClusterBuilder::new()
+        .add_body(5.972e24, vec3(0., 0., 0.), vec3(0., 0., 0.))
+        .add_body(7.342e22, vec3(3.844e8, 0., 0.), vec3(0., 1.022e3, 0.))
+        .build();
+    let mut frame = simulator.snapshot();
+    if truncate {
+        frame.positions.pop();
+    }
+    let path = env::temp_dir().join(name);
+    fs::write(&path, serde_json::to_string(&frame).unwrap() + "\n").unwrap();
+    let replay = Replay::from_file(&path);
+    fs::remove_file(&path).ok();
+    replay.map(|replay| replay.len()).map_err(|err| err.to_string())
+}
+
+#[test]
+fn replay_loads_consistent_frames() {
+    assert_eq!(write_frames("nbodies-replay-valid.jsonl", false), Ok(1));
+}
+
+#[test]
+fn replay_rejects_frames_with_missing_positions() {
+    let err = write_frames("nbodies-replay-invalid.jsonl", true).unwrap_err();
+    assert!(err.contains("inconsistent frame 1"));
+}
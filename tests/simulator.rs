@@ -0,0 +1,27 @@
+use geomath::vector::vec3;
+
+use nbodies::core::ClusterBuilder;
+use nbodies::physics;
+use nbodies::physics::G_UNIV;
+
+#[test]
+fn diverged_body_is_removed_and_the_rest_keeps_running() {
+    let mut simulator = ClusterBuilder::new()
+        .add_body(5.972e24, vec3(0., 0., 0.), vec3(0., 0., 0.))
+        .add_body(7.342e22, vec3(3.844e8, 0., 0.), vec3(0., 1.022e3, 0.))
+        .add_body(1e3, vec3(-1e7, 0., 0.), vec3(0., 0., 0.))
+        .build();
+    simulator.cluster.points[2].state.speed.x = std::f64::INFINITY;
+    let removed = simulator.remove_diverged();
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].0, 2);
+    assert_eq!(simulator.cluster.len(), 2);
+    let diverged = simulator.apply(60., 10, |points, _, i| {
+        let acceleration = physics::gravity_acceleration(&points[i], points, G_UNIV, 0.);
+        physics::derivative(&points[i].state.speed, &acceleration)
+    });
+    assert!(diverged.is_empty());
+    for point in simulator.cluster.points.iter() {
+        assert!(point.state.position.x.is_finite() && point.state.speed.y.is_finite());
+    }
+}
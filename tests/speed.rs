@@ -0,0 +1,24 @@
+use geomath::vector::vec3;
+
+use nbodies::common::{Scale, SPEED_SCALING_FACTOR};
+use nbodies::core::SpeedMode;
+
+#[test]
+fn screen_speed_covers_the_cursor_offset_in_one_real_second() {
+    let scale = Scale::new(2e6, 1e-9);
+    let offset = vec3(4e9, -2e9, 0.);
+    let speed = SpeedMode::Screen.speed(&offset, &scale);
+    assert_eq!(speed.x, 2e3);
+    assert_eq!(speed.y, -1e3);
+    assert_eq!(speed.z, 0.);
+}
+
+#[test]
+fn world_speed_ignores_the_time_scale() {
+    let offset = vec3(4e9, -2e9, 0.);
+    let slow = SpeedMode::World.speed(&offset, &Scale::new(1., 1e-9));
+    let fast = SpeedMode::World.speed(&offset, &Scale::new(2e6, 1e-9));
+    assert_eq!(slow.x, offset.x * SPEED_SCALING_FACTOR);
+    assert_eq!(slow.x, fast.x);
+    assert_eq!(slow.y, fast.y);
+}
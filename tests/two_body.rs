@@ -0,0 +1,125 @@
+use dynamics::orbital::Orbit;
+use dynamics::solver::Method;
+use geomath::prelude::Metric;
+use geomath::vector::vec3;
+
+use nbodies::App;
+use nbodies::core::{ClusterBuilder, Config};
+use nbodies::orbit::Kepler;
+use nbodies::physics::G_UNIV;
+
+const PRIMARY_MASS: f64 = 5.972e24;
+const SATELLITE_MASS: f64 = 1.;
+const RADIUS: f64 = 7e6;
+const STEPS: usize = 100;
+const OVERSAMPLING: u32 = 100;
+
+// Relative error allowed after one period with 10^4 integration steps per orbit.
+// The fourth order Runge-Kutta stays close to rounding, lower order methods drift
+// by a fraction of a percent.
+fn tolerance(method: Method) -> f64 {
+    if method == Method::RungeKutta4 { 1e-6 } else { 1e-2 }
+}
+
+fn kepler_pair(method: Method) -> App {
+    let speed = (G_UNIV * PRIMARY_MASS / RADIUS).sqrt();
+    let simulator = ClusterBuilder::new()
+        .method(method)
+        .add_body(PRIMARY_MASS, vec3(0., 0., 0.), vec3(0., 0., 0.))
+        .add_body(SATELLITE_MASS, vec3(RADIUS, 0., 0.), vec3(0., speed, 0.))
+        .build();
+    let mut config = Config::default();
+    config.method = method;
+    config.oversampling = OVERSAMPLING;
+    App::new(simulator, config)
+}
+
+fn for_each_method<F: FnMut(Method)>(mut f: F) {
+    let first = Method::RungeKutta4;
+    let mut method = first;
+    loop {
+        f(method);
+        method.next();
+        if method == first {
+            break;
+        }
+    }
+}
+
+#[test]
+fn period_matches_kepler_third_law() {
+    let speed = (G_UNIV * PRIMARY_MASS / RADIUS).sqrt();
+    let orbit = Orbit::from_state(&vec3(RADIUS, 0., 0.), &vec3(0., speed, 0.), G_UNIV * PRIMARY_MASS);
+    let expected = 2. * std::f64::consts::PI * (RADIUS.powi(3) / (G_UNIV * PRIMARY_MASS)).sqrt();
+    assert!((orbit.period() - expected).abs() / expected < 1e-9);
+}
+
+#[test]
+fn circular_orbit_returns_to_start_after_one_period() {
+    for_each_method(|method| {
+        let mut app = kepler_pair(method);
+        let start = app.simulator.cluster[1].state.position - app.simulator.cluster[0].state.position;
+        let start_speed = app.simulator.cluster[1].state.speed - app.simulator.cluster[0].state.speed;
+        let period = Orbit::from_state(&start, &start_speed, G_UNIV * PRIMARY_MASS).period();
+        app.config.scale.time = period / STEPS as f64;
+        for _ in 0..STEPS {
+            app.step(1.);
+        }
+        let end = app.simulator.cluster[1].state.position - app.simulator.cluster[0].state.position;
+        let end_speed = app.simulator.cluster[1].state.speed - app.simulator.cluster[0].state.speed;
+        let position_error = (end - start).magnitude() / start.magnitude();
+        let speed_error = (end_speed - start_speed).magnitude() / start_speed.magnitude();
+        assert!(position_error < tolerance(method), "{:?} position error {:e}", method, position_error);
+        assert!(speed_error < tolerance(method), "{:?} speed error {:e}", method, speed_error);
+    });
+}
+
+#[test]
+fn circular_orbit_conserves_energy() {
+    for_each_method(|method| {
+        let mut app = kepler_pair(method);
+        let start = app.simulator.cluster[1].state.position - app.simulator.cluster[0].state.position;
+        let start_speed = app.simulator.cluster[1].state.speed - app.simulator.cluster[0].state.speed;
+        let period = Orbit::from_state(&start, &start_speed, G_UNIV * PRIMARY_MASS).period();
+        app.config.scale.time = period / STEPS as f64;
+        for _ in 0..STEPS {
+            app.step(1.);
+        }
+        let drift = app.simulator.energy_history.max_drift;
+        assert!(drift < tolerance(method), "{:?} energy drift {:e}", method, drift);
+    });
+}
+
+#[test]
+fn prediction_follows_the_orbit_without_moving_the_cluster() {
+    let app = kepler_pair(Method::RungeKutta4);
+    let start = app.simulator.cluster[1].state.position;
+    let speed = app.simulator.cluster[1].state.speed;
+    let period = Orbit::from_state(&start, &speed, G_UNIV * PRIMARY_MASS).period();
+    let positions = app.simulator.predict(1, period, 0.);
+    let end = positions.last().unwrap();
+    assert!((*end - start).magnitude() / start.magnitude() < 1e-3);
+    assert!((positions[positions.len() / 2] - start).magnitude() > RADIUS);
+    assert_eq!(app.simulator.cluster[1].state.position.x, start.x);
+    assert_eq!(app.simulator.cluster[1].state.position.y, start.y);
+}
+
+#[test]
+fn forward_then_backward_returns_to_start() {
+    let mut app = kepler_pair(Method::RungeKutta4);
+    let start = app.simulator.cluster[1].state.position;
+    let start_speed = app.simulator.cluster[1].state.speed;
+    let period = Orbit::from_state(&start, &start_speed, G_UNIV * PRIMARY_MASS).period();
+    app.config.scale.time = period / STEPS as f64;
+    for _ in 0..STEPS / 4 {
+        app.step(1.);
+    }
+    app.config.reverse = true;
+    for _ in 0..STEPS / 4 {
+        app.step(1.);
+    }
+    let end = app.simulator.cluster[1].state.position;
+    let end_speed = app.simulator.cluster[1].state.speed;
+    assert!((end - start).magnitude() / start.magnitude() < 1e-9);
+    assert!((end_speed - start_speed).magnitude() / start_speed.magnitude() < 1e-9);
+}